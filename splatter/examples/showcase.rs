@@ -3,7 +3,7 @@ use geometric_algebra::{
     GeometricProduct, One, Signum, Transformation,
 };
 use splatter::{
-    renderer::{Configuration, DepthSorting, Renderer},
+    renderer::{Configuration, DepthSorting, ModelTransform, Renderer},
     scene::Scene,
 };
 use std::{collections::HashSet, env, fs::File};
@@ -38,7 +38,12 @@ impl application_framework::Application for Application {
             device,
             Configuration {
                 surface_configuration: surface_configuration.clone(),
+                sample_count: 1,
+                depth_format: None,
+                reversed_depth: false,
+                write_depth: false,
                 depth_sorting: DepthSorting::Gpu,
+                cpu_sorting_thread_count: 1,
                 use_covariance_for_scale: true,
                 use_unaligned_rectangles: true,
                 spherical_harmonics_order: 1,
@@ -49,13 +54,14 @@ impl application_framework::Application for Application {
                 splat_scale: 1.0,
             },
         );
-        let (file_header_size, splat_count, mut file) = Scene::parse_file_header(file);
-        let mut scene = Scene::new(device, &renderer, splat_count);
+        let (file_header_size, splat_count, mut file) = Scene::parse_file_header(file).unwrap();
+        let mut scene = Scene::new();
+        scene.allocate(device, splat_count);
         let chunks_left_to_load = if LOAD_CHUNK_SIZE == 0 {
-            scene.load_chunk(queue, &mut file, file_header_size, 0..splat_count);
+            scene.load_chunk(queue, &mut file, file_header_size, 0..splat_count).unwrap();
             0
         } else {
-            (scene.splat_count + LOAD_CHUNK_SIZE - 1) / LOAD_CHUNK_SIZE
+            splat_count.div_ceil(LOAD_CHUNK_SIZE)
         };
         Self {
             renderer,
@@ -98,7 +104,9 @@ impl application_framework::Application for Application {
         if self.chunks_left_to_load > 0 {
             self.chunks_left_to_load -= 1;
             let load_range = self.chunks_left_to_load * LOAD_CHUNK_SIZE..(self.chunks_left_to_load + 1) * LOAD_CHUNK_SIZE;
-            self.scene.load_chunk(queue, &mut self.file, self.file_header_size, load_range);
+            self.scene
+                .load_chunk(queue, &mut self.file, self.file_header_size, load_range)
+                .unwrap();
             queue.submit([]);
         }
         for keycode in &self.pressed_keys {
@@ -139,14 +147,21 @@ impl application_framework::Application for Application {
         }
         let camera_motor = self.camera_translation.geometric_product(self.camera_rotation);
         let frame_view = frame.texture.create_view(&wgpu::TextureViewDescriptor::default());
-        self.renderer
-            .render_frame(device, queue, &frame_view, self.viewport_size, camera_motor, &self.scene);
+        self.renderer.render_frame(
+            device,
+            queue,
+            &frame_view,
+            None,
+            self.viewport_size,
+            camera_motor,
+            &[(&self.scene, ModelTransform::default())],
+        );
     }
 
     fn mouse_motion(&mut self, delta: (f64, f64)) {
         let position = [
-            -1.0 * std::f32::consts::PI * (delta.0 as f32 / self.viewport_size.width as f32),
-            -1.0 * std::f32::consts::PI * (delta.1 as f32 / self.viewport_size.height as f32),
+            -std::f32::consts::PI * (delta.0 as f32 / self.viewport_size.width as f32),
+            -std::f32::consts::PI * (delta.1 as f32 / self.viewport_size.height as f32),
         ];
         self.camera_rotation = self
             .camera_rotation
@@ -0,0 +1,57 @@
+//! Bevy asset integration, splat files are loaded asynchronously on the IO task pool and reloaded when they change
+use crate::scene::{GpuSplat, LoadError, Scene};
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+    utils::BoxedFuture,
+};
+use std::io::Cursor;
+
+/// The splats of one file, shared by all entities which hold a handle to it
+#[derive(Asset, TypePath, Clone, Debug, Default)]
+pub struct GaussianCloud {
+    pub splats: Vec<GpuSplat>,
+}
+
+/// Loads [GaussianCloud]s from `.ply`, `.splat` and `.spz` files, depending on the enabled cargo features
+#[derive(Default)]
+pub struct GaussianCloudLoader;
+
+impl AssetLoader for GaussianCloudLoader {
+    type Asset = GaussianCloud;
+    type Settings = ();
+    type Error = LoadError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<GaussianCloud, LoadError>> {
+        Box::pin(async move {
+            let extension = load_context
+                .path()
+                .extension()
+                .map(|extension| extension.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            let mut data = Vec::new();
+            reader.read_to_end(&mut data).await?;
+            let mut scene = Scene::new();
+            scene.load_splat_reader(&mut Cursor::new(data), &extension)?;
+            Ok(GaussianCloud { splats: scene.splat_data })
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        Scene::SUPPORTED_EXTENSIONS
+    }
+}
+
+/// Registers [GaussianCloud] and its loader
+pub struct GaussianCloudPlugin;
+
+impl Plugin for GaussianCloudPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<GaussianCloud>().init_asset_loader::<GaussianCloudLoader>();
+    }
+}
@@ -1,14 +1,40 @@
+use crate::asset::GaussianCloud;
 use bevy::prelude::*;
+use bevy::render::extract_component::ExtractComponent;
 
-#[derive(Component)]
+/// A cloud of gaussian splats, placed in the world by the [Transform] of its entity
+#[derive(Component, Clone, Debug, Default)]
 pub struct GaussianSplat {
-    pub splat_file: String,
+    pub cloud: Handle<GaussianCloud>,
 }
 
-#[derive(Bundle)]
+/// Per entity render settings of a [GaussianSplat]
+#[derive(Component, Clone, Debug)]
+pub struct GaussianSplatSettings {
+    /// Factor to scale the splat ellipsoids with, in addition to the global configuration
+    pub splat_scale: f32,
+}
+
+impl Default for GaussianSplatSettings {
+    fn default() -> Self {
+        Self { splat_scale: 1.0 }
+    }
+}
+
+/// Splat rendering settings of a [Camera], cameras without it use the defaults
+#[derive(Component, ExtractComponent, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GaussianSplatCameraSettings {
+    /// Writes the approximate depth of dense splats, so that meshes drawn after them are occluded. Costs an extra pass
+    pub write_depth: bool,
+}
+
+#[derive(Bundle, Clone, Debug, Default)]
 pub struct GaussianSplatBundle {
     pub splat: GaussianSplat,
+    pub settings: GaussianSplatSettings,
     pub transform: Transform,
     pub global_transform: GlobalTransform,
     pub visibility: Visibility,
+    pub inherited_visibility: InheritedVisibility,
+    pub view_visibility: ViewVisibility,
 }
@@ -0,0 +1,419 @@
+//! Reference implementation of shaders.wgsl on the CPU
+//!
+//! Follows the vertex and fragment stages of the GPU pipeline step by step, so that rendering can be tested without a GPU.
+//! Splats are sorted back to front by their exact depth instead of the quantized keys of the radix sort,
+//! which is the only intended difference to the GPU output.
+use crate::{
+    renderer::{Camera, Configuration, ModelTransform},
+    scene::{GpuSplat, Scene},
+    utils::{mat4_multiplication, mat4_orthonormal_inverse, mat4_transform, perspective_projection},
+};
+use geometric_algebra::ppga3d::Point;
+
+/// Column major 3x3 matrix, like `mat3x3<f32>` in WGSL
+type Mat3 = [[f32; 3]; 3];
+
+/// Spherical harmonics coefficients, `shc` in shaders.wgsl
+const SHC: [f32; 16] = [
+    0.282_094_8,
+    -0.488_602_5,
+    0.488_602_5,
+    -0.488_602_5,
+    1.092_548_4,
+    -1.092_548_4,
+    0.315_391_57,
+    -1.092_548_4,
+    0.546_274_2,
+    -0.590_043_6,
+    2.890_611_4,
+    -0.457_045_8,
+    0.373_176_33,
+    -0.457_045_8,
+    1.445_305_7,
+    -0.590_043_6,
+];
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn normalize(a: [f32; 3]) -> [f32; 3] {
+    let inverse_length = 1.0 / dot(a, a).sqrt();
+    a.map(|value| value * inverse_length)
+}
+
+/// `sign()` of WGSL, which unlike [f32::signum] is zero for zero
+fn sign(value: f32) -> f32 {
+    if value > 0.0 {
+        1.0
+    } else if value < 0.0 {
+        -1.0
+    } else {
+        0.0
+    }
+}
+
+fn transpose(a: &Mat3) -> Mat3 {
+    [0, 1, 2].map(|column| [0, 1, 2].map(|row| a[row][column]))
+}
+
+/// `a * b` in WGSL
+fn mat3_multiplication(a: &Mat3, b: &Mat3) -> Mat3 {
+    b.map(|column| mat3_transform(a, column))
+}
+
+/// `a * b` in WGSL, with `b` being a vector
+fn mat3_transform(a: &Mat3, b: [f32; 3]) -> [f32; 3] {
+    [0, 1, 2].map(|row| a[0][row] * b[0] + a[1][row] * b[1] + a[2][row] * b[2])
+}
+
+/// `a * b` in WGSL, with `a` being a vector
+fn vec3_transform(a: [f32; 3], b: &Mat3) -> [f32; 3] {
+    b.map(|column| dot(a, column))
+}
+
+fn upper_mat3(a: &[Point; 4]) -> Mat3 {
+    [0, 1, 2].map(|column| [a[column][0], a[column][1], a[column][2]])
+}
+
+fn quat_to_mat(p: [f32; 4]) -> Mat3 {
+    let [x, y, z, w] = p.map(|value| value * std::f32::consts::SQRT_2);
+    let (yy, yz, yw, yx) = (y * y, y * z, y * w, y * x);
+    let (zz, zw, zx) = (z * z, z * w, z * x);
+    let (ww, wx) = (w * w, w * x);
+    [
+        [1.0 - zz - ww, yz + wx, yw - zx],
+        [yz - wx, 1.0 - yy - ww, zw + yx],
+        [yw + zx, zw - yx, 1.0 - yy - zz],
+    ]
+}
+
+/// Everything of `Uniforms` in shaders.wgsl which is needed on the CPU
+struct View {
+    camera_matrix: [Point; 4],
+    view_matrix: [Point; 4],
+    view_projection_matrix: [Point; 4],
+    view_size: [f32; 2],
+    ellipse_size_bias: f32,
+}
+
+impl View {
+    fn camera_position(&self) -> [f32; 3] {
+        [self.camera_matrix[3][0], self.camera_matrix[3][1], self.camera_matrix[3][2]]
+    }
+
+    fn world_to_clip_space(&self, world_position: [f32; 3]) -> [f32; 3] {
+        let homogenous_position = mat4_transform(
+            &self.view_projection_matrix,
+            &Point::new(world_position[0], world_position[1], world_position[2], 1.0),
+        );
+        let w = homogenous_position[3] + 0.0000001;
+        [homogenous_position[0] / w, homogenous_position[1] / w, homogenous_position[2] / w]
+    }
+
+    fn projected_covariance_of_ellipsoid(&self, scale: [f32; 3], rotation: &Mat3, translation: [f32; 3]) -> Mat3 {
+        let camera_matrix = upper_mat3(&self.camera_matrix);
+        let mut transform = *rotation;
+        for (column, scale) in transform.iter_mut().zip(scale) {
+            *column = column.map(|value| value * scale);
+        }
+        let view_position = mat4_transform(&self.view_matrix, &Point::new(translation[0], translation[1], translation[2], 1.0));
+        let z = view_position[2];
+        let x = (view_position[0] / z).clamp(-1.0, 1.0) * z;
+        let y = (view_position[1] / z).clamp(-1.0, 1.0) * z;
+        let jacobian = [[1.0 / z, 0.0, -x / (z * z)], [0.0, 1.0 / z, -y / (z * z)], [0.0; 3]];
+        let t = mat3_multiplication(&mat3_multiplication(&transpose(&transform), &camera_matrix), &jacobian);
+        mat3_multiplication(&transpose(&t), &t)
+    }
+
+    fn projected_contour_of_ellipsoid(&self, scale: [f32; 3], rotation: &Mat3, translation: [f32; 3]) -> Mat3 {
+        let camera_matrix = upper_mat3(&self.camera_matrix);
+        let mut transform = *rotation;
+        for (column, scale) in transform.iter_mut().zip(scale) {
+            *column = column.map(|value| value / scale);
+        }
+        let camera_position = self.camera_position();
+        let ray_origin = [0, 1, 2].map(|axis| camera_position[axis] - translation[axis]);
+        let o = vec3_transform(ray_origin, &transform);
+        let o2 = o.map(|value| value * value);
+        // Bounding cone of the ellipsoid with its vertex at the camera position
+        let diagonal = [1.0 - o2[1] - o2[2], 1.0 - o2[0] - o2[2], 1.0 - o2[0] - o2[1]];
+        let triangle = [o[1] * o[2], o[0] * o[2], o[0] * o[1]];
+        let a = [
+            [diagonal[0], triangle[2], triangle[1]],
+            [triangle[2], diagonal[1], triangle[0]],
+            [triangle[1], triangle[0], diagonal[2]],
+        ];
+        let transform = mat3_multiplication(&transpose(&camera_matrix), &transform);
+        mat3_multiplication(&mat3_multiplication(&transform, &a), &transpose(&transform))
+    }
+}
+
+fn extract_translation_of_ellipse(m: &Mat3) -> [f32; 2] {
+    let inverse_discriminant = 1.0 / (m[0][0] * m[1][1] - m[0][1] * m[0][1]);
+    [
+        (m[0][1] * m[1][2] - m[1][1] * m[0][2]) * inverse_discriminant,
+        (m[0][1] * m[0][2] - m[0][0] * m[1][2]) * inverse_discriminant,
+    ]
+}
+
+fn extract_rotation_of_ellipse(m: &Mat3) -> [f32; 2] {
+    let a = (m[0][0] - m[1][1]) * (m[0][0] - m[1][1]);
+    let b = a + 4.0 * m[0][1] * m[0][1];
+    let c = 0.5 * (a / b).sqrt();
+    let mut j = (0.5 - c).sqrt();
+    let mut k = -(0.5 + c).sqrt() * sign(m[0][1]) * sign(m[0][0] - m[1][1]);
+    if m[0][1] < 0.0 || m[0][0] - m[1][1] < 0.0 {
+        k = -k;
+        j = -j;
+    }
+    if m[0][0] - m[1][1] < 0.0 {
+        (j, k) = (-k, j);
+    }
+    [j, k]
+}
+
+fn extract_scale_of_ellipse(m: &Mat3, translation: [f32; 2], rotation: [f32; 2]) -> [f32; 2] {
+    let d = 2.0 * m[0][1] * rotation[0] * rotation[1];
+    let e = m[2][2]
+        - (m[0][0] * translation[0] * translation[0] + m[1][1] * translation[1] * translation[1] + 2.0 * m[0][1] * translation[0] * translation[1]);
+    let semi_major_axis = (e / (m[0][0] * rotation[1] * rotation[1] + m[1][1] * rotation[0] * rotation[0] - d))
+        .abs()
+        .sqrt();
+    let semi_minor_axis = (e / (m[0][0] * rotation[0] * rotation[0] + m[1][1] * rotation[1] * rotation[1] + d))
+        .abs()
+        .sqrt();
+    [semi_major_axis, semi_minor_axis]
+}
+
+fn extract_scale_of_covariance(m: &Mat3) -> [f32; 2] {
+    let a = (m[0][0] - m[1][1]) * (m[0][0] - m[1][1]);
+    let b = (a + 4.0 * m[0][1] * m[0][1]).sqrt();
+    [((m[0][0] + m[1][1] + b) * 0.5).sqrt(), ((m[0][0] + m[1][1] - b) * 0.5).sqrt()]
+}
+
+fn spherical_harmonics_lookup(ray_direction: [f32; 3], splat: &GpuSplat, spherical_harmonics_order: usize) -> [f32; 3] {
+    let [x, y, z] = ray_direction;
+    let [xx, yy, zz] = ray_direction.map(|value| value * value);
+    let bands = [
+        1.0,
+        y,
+        z,
+        x,
+        x * y,
+        y * z,
+        2.0 * zz - xx - yy,
+        x * z,
+        xx - yy,
+        y * (3.0 * xx - yy),
+        x * y * z,
+        y * (4.0 * zz - xx - yy),
+        z * (2.0 * zz - 3.0 * xx - 3.0 * yy),
+        x * (4.0 * zz - xx - yy),
+        z * (xx - yy),
+        x * (xx - 3.0 * yy),
+    ];
+    let coefficient_count = (spherical_harmonics_order.min(3) + 1).pow(2);
+    let mut color = [0.5; 3];
+    for coefficient in 0..coefficient_count {
+        for (channel, color) in color.iter_mut().enumerate() {
+            *color += SHC[coefficient] * splat.color_sh[coefficient * 3 + channel] * bands[coefficient];
+        }
+    }
+    color
+}
+
+/// A splat after the vertex stage, ready to be rasterized
+struct ProjectedSplat {
+    /// Clip space depth, which the splats are sorted by
+    depth: f32,
+    color: [f32; 4],
+    /// Maps texture coordinates to view plane positions, the columns are the two semi axes and the center
+    transformation: [[f32; 2]; 3],
+    /// Half extent of the rasterized quad, in texture coordinates if `unaligned` and in view plane units otherwise
+    extent: f32,
+    unaligned: bool,
+}
+
+/// Renders splats on the CPU, for tests and offline thumbnails
+pub struct CpuRenderer {
+    config: Configuration,
+}
+
+impl CpuRenderer {
+    /// Constructs a new [CpuRenderer], only the parts of `config` which affect the shaders are used
+    pub fn new(config: Configuration) -> Self {
+        Self { config }
+    }
+
+    /// The configuration which is currently in use
+    pub fn config(&self) -> &Configuration {
+        &self.config
+    }
+
+    /// Renders the given `scenes` into a `width` x `height` RGBA8 buffer of premultiplied colors, row by row from the top
+    ///
+    /// The buffer starts out transparent black, which is blended with [wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING].
+    /// The colors are stored as they are, like in a `Rgba8Unorm` texture.
+    pub fn render(&self, camera: &Camera, width: u32, height: u32, scenes: &[(&Scene, ModelTransform)]) -> Vec<u8> {
+        let [view_width, view_height] = camera.view_size;
+        let view_matrix = mat4_orthonormal_inverse(&camera.matrix);
+        let projection_matrix = perspective_projection(view_width, view_height, camera.near, camera.far);
+        let view = View {
+            camera_matrix: camera.matrix,
+            view_matrix,
+            view_projection_matrix: mat4_multiplication(&projection_matrix, &view_matrix),
+            view_size: camera.view_size,
+            ellipse_size_bias: 0.2 * view_width / width as f32,
+        };
+        let mut projected_splats = Vec::new();
+        for (scene, model_transform) in scenes {
+            let model_matrix = model_transform.matrix();
+            let model_rotation = upper_mat3(&model_matrix).map(|column| column.map(|value| value / model_transform.scale));
+            for splat in &scene.splat_data {
+                let world_position = mat4_transform(&model_matrix, &Point::new(splat.center[0], splat.center[1], splat.center[2], 1.0));
+                let world_position = [world_position[0], world_position[1], world_position[2]];
+                if let Some(projected_splat) = self.project_splat(&view, splat, world_position, &model_rotation, model_transform) {
+                    projected_splats.push(projected_splat);
+                }
+            }
+        }
+        // Back to front
+        projected_splats.sort_by(|a, b| b.depth.total_cmp(&a.depth));
+        let mut pixels = vec![[0.0f32; 4]; width as usize * height as usize];
+        for projected_splat in &projected_splats {
+            Self::rasterize(&view, projected_splat, width, height, &mut pixels);
+        }
+        pixels
+            .iter()
+            .flat_map(|pixel| pixel.map(|value| (value.clamp(0.0, 1.0) * 255.0).round() as u8))
+            .collect()
+    }
+
+    /// Vertex stage, returns [None] for culled splats
+    fn project_splat(
+        &self,
+        view: &View,
+        splat: &GpuSplat,
+        world_position: [f32; 3],
+        model_rotation: &Mat3,
+        model_transform: &ModelTransform,
+    ) -> Option<ProjectedSplat> {
+        let clip_space_position = view.world_to_clip_space(world_position);
+        let tolerance = self.config.frustum_culling_tolerance;
+        if !(clip_space_position[0].abs() < tolerance && clip_space_position[1].abs() < tolerance && (clip_space_position[2] - 0.5).abs() < 0.5) {
+            return None;
+        }
+        let world_rotation = mat3_multiplication(model_rotation, &quat_to_mat(splat.rotation));
+        let world_scale = splat
+            .scale
+            .map(|value| value * model_transform.scale * model_transform.splat_scale * self.config.splat_scale);
+        // The spherical harmonics are defined in model space
+        let camera_position = view.camera_position();
+        let ray_direction = vec3_transform(
+            normalize([0, 1, 2].map(|axis| world_position[axis] - camera_position[axis])),
+            model_rotation,
+        );
+        let [red, green, blue] = spherical_harmonics_lookup(ray_direction, splat, self.config.spherical_harmonics_order);
+        let m = view.projected_contour_of_ellipsoid(world_scale, &world_rotation, world_position);
+        let translation = extract_translation_of_ellipse(&m);
+        let rotation = extract_rotation_of_ellipse(&m);
+        let semi_axes = if self.config.use_covariance_for_scale {
+            extract_scale_of_covariance(&view.projected_covariance_of_ellipsoid(world_scale, &world_rotation, world_position))
+        } else {
+            extract_scale_of_ellipse(&m, translation, rotation)
+        };
+        let transformation = [
+            [
+                rotation[1] * (view.ellipse_size_bias + semi_axes[0]),
+                -rotation[0] * (view.ellipse_size_bias + semi_axes[0]),
+            ],
+            [
+                rotation[0] * (view.ellipse_size_bias + semi_axes[1]),
+                rotation[1] * (view.ellipse_size_bias + semi_axes[1]),
+            ],
+            translation,
+        ];
+        let extent = if self.config.use_unaligned_rectangles {
+            self.config.ellipse_margin
+        } else {
+            let [x, y, _] = transformation;
+            (x[0] * x[0] + x[1] * x[1]).max(y[0] * y[0] + y[1] * y[1]).sqrt() * self.config.ellipse_margin
+        };
+        if !transformation.iter().flatten().chain([&extent]).all(|value| value.is_finite()) {
+            return None;
+        }
+        Some(ProjectedSplat {
+            depth: clip_space_position[2],
+            color: [red, green, blue, splat.alpha],
+            transformation,
+            extent,
+            unaligned: self.config.use_unaligned_rectangles,
+        })
+    }
+
+    /// Fragment stage and blending, for all pixels whose center is covered by the quad of the splat
+    fn rasterize(view: &View, splat: &ProjectedSplat, width: u32, height: u32, pixels: &mut [[f32; 4]]) {
+        let [x, y, center] = splat.transformation;
+        let determinant = x[0] * y[1] - x[1] * y[0];
+        if determinant == 0.0 {
+            return;
+        }
+        let inverse = [[y[1] / determinant, -x[1] / determinant], [-y[0] / determinant, x[0] / determinant]];
+        // Corners of the quad on the view plane
+        let corners = [[-1.0, -1.0], [-1.0, 1.0], [1.0, -1.0], [1.0, 1.0]].map(|[u, v]: [f32; 2]| {
+            let (u, v) = (u * splat.extent, v * splat.extent);
+            if splat.unaligned {
+                [center[0] + x[0] * u + y[0] * v, center[1] + x[1] * u + y[1] * v]
+            } else {
+                [center[0] + u, center[1] + v]
+            }
+        });
+        // View plane to pixel coordinates
+        let to_pixel = |position: [f32; 2]| {
+            [
+                (position[0] / view.view_size[0] * 0.5 + 0.5) * width as f32,
+                (0.5 - position[1] / view.view_size[1] * 0.5) * height as f32,
+            ]
+        };
+        let (mut min, mut max) = ([f32::MAX; 2], [f32::MIN; 2]);
+        for corner in corners.map(to_pixel) {
+            for axis in 0..2 {
+                min[axis] = min[axis].min(corner[axis]);
+                max[axis] = max[axis].max(corner[axis]);
+            }
+        }
+        let columns = (min[0] - 0.5).ceil().max(0.0) as u32..((max[0] - 0.5).floor() + 1.0).clamp(0.0, width as f32) as u32;
+        let rows = (min[1] - 0.5).ceil().max(0.0) as u32..((max[1] - 0.5).floor() + 1.0).clamp(0.0, height as f32) as u32;
+        for row in rows {
+            for column in columns.clone() {
+                let position = [
+                    ((column as f32 + 0.5) / width as f32 * 2.0 - 1.0) * view.view_size[0],
+                    (1.0 - (row as f32 + 0.5) / height as f32 * 2.0) * view.view_size[1],
+                ];
+                let offset = [position[0] - center[0], position[1] - center[1]];
+                let tex_coord = [
+                    inverse[0][0] * offset[0] + inverse[1][0] * offset[1],
+                    inverse[0][1] * offset[0] + inverse[1][1] * offset[1],
+                ];
+                let inside = if splat.unaligned {
+                    tex_coord[0].abs() <= splat.extent && tex_coord[1].abs() <= splat.extent
+                } else {
+                    offset[0].abs() <= splat.extent && offset[1].abs() <= splat.extent
+                };
+                if !inside {
+                    continue;
+                }
+                let alpha = splat.color[3] * (-0.5 * (tex_coord[0] * tex_coord[0] + tex_coord[1] * tex_coord[1])).exp();
+                if alpha < 1.0 / 255.0 {
+                    continue;
+                }
+                let pixel = &mut pixels[(row * width + column) as usize];
+                let source = [splat.color[0] * alpha, splat.color[1] * alpha, splat.color[2] * alpha, alpha];
+                for (destination, source) in pixel.iter_mut().zip(source) {
+                    *destination = source + *destination * (1.0 - alpha);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,373 @@
+//! Depth sorting of splats on the CPU, used by [DepthSorting::Cpu](crate::renderer::DepthSorting::Cpu)
+//! and [DepthSorting::CpuBackground](crate::renderer::DepthSorting::CpuBackground)
+
+use crate::utils::mat4_transform;
+use geometric_algebra::ppga3d::Point;
+use std::sync::{Arc, Condvar, Mutex};
+
+const RADIX_BITS_PER_DIGIT: u32 = 8;
+const RADIX_BASE: usize = 1 << RADIX_BITS_PER_DIGIT;
+
+/// Below this many entries spawning threads costs more than it saves
+const MIN_ENTRIES_PER_THREAD: usize = 1 << 14;
+
+/// Key of culled splats, which are sorted behind all others and left out of the result
+const CULLED_KEY: u32 = u32::MAX;
+
+/// Maps a float to an integer of the same order, including negative values and infinities
+///
+/// The sign bit is flipped for positive values and all bits for negative ones.
+pub fn order_preserving_key(value: f32) -> u32 {
+    let bits = value.to_bits();
+    if bits & 0x8000_0000 == 0 {
+        bits | 0x8000_0000
+    } else {
+        !bits
+    }
+}
+
+/// Key of a splat at `depth`, farther splats get smaller keys so that ascending keys are back to front
+fn sort_key(depth: Option<f32>) -> u32 {
+    match depth {
+        Some(depth) if !depth.is_nan() => !order_preserving_key(depth),
+        _ => CULLED_KEY,
+    }
+}
+
+/// Placement of the models of a frame relative to the camera, which determines the depths of their splats
+#[derive(Clone, Default)]
+pub struct SortView {
+    /// Transforms from model space to clip space, one per model
+    pub model_view_projection_matrices: Vec<[Point; 4]>,
+    /// Index of the first splat of each model, in ascending order
+    pub model_splat_offsets: Vec<usize>,
+    /// Splats this far outside the frustum in normalized device coordinates are culled
+    pub frustum_culling_tolerance: f32,
+}
+
+impl SortView {
+    /// Index of the model which the splat at `splat_index` belongs to
+    pub fn model_of(&self, splat_index: usize) -> Option<usize> {
+        self.model_splat_offsets.partition_point(|offset| *offset <= splat_index).checked_sub(1)
+    }
+
+    /// Depth in clip space of a splat at `position` in the space of model `model_index`, [None] if it is culled
+    pub fn depth_of(&self, model_index: usize, position: &[f32]) -> Option<f32> {
+        let homogenous_position = mat4_transform(
+            &self.model_view_projection_matrices[model_index],
+            &Point::new(position[0], position[1], position[2], 1.0),
+        );
+        let clip_space_position = homogenous_position * (1.0 / homogenous_position[3]);
+        (clip_space_position[0].abs() < self.frustum_culling_tolerance
+            && clip_space_position[1].abs() < self.frustum_culling_tolerance
+            && (clip_space_position[2] - 0.5).abs() < 0.5)
+            .then_some(clip_space_position[2])
+    }
+}
+
+/// Writes entries to disjoint indices of a slice from multiple threads
+struct SharedOutput(*mut (u32, u32));
+
+unsafe impl Send for SharedOutput {}
+unsafe impl Sync for SharedOutput {}
+
+impl SharedOutput {
+    /// Safety: `index` has to be in bounds and must not be written by any other thread
+    unsafe fn write(&self, index: usize, entry: (u32, u32)) {
+        *self.0.add(index) = entry;
+    }
+}
+
+/// Back to front sorter of splats by their depth, which keeps its buffers across frames
+///
+/// Sorting is stable with respect to the order of the previous frame. Thus, if the camera moves little,
+/// most splats stay in place and an insertion sort finishes faster than the LSD radix sort it falls back to.
+pub struct CpuSorter {
+    /// Number of threads to compute keys and radix sort with, 1 sorts on the calling thread only
+    pub thread_count: usize,
+    /// Splat indices sorted back to front in the previous frame, including the culled ones at the end
+    order: Vec<u32>,
+    /// Pairs of key and splat index
+    entries: Vec<(u32, u32)>,
+    scratch: Vec<(u32, u32)>,
+    visible_count: usize,
+    incremental: bool,
+}
+
+impl Default for CpuSorter {
+    fn default() -> Self {
+        Self::new(std::thread::available_parallelism().map_or(1, usize::from))
+    }
+}
+
+impl CpuSorter {
+    /// Constructs a new [CpuSorter] using up to `thread_count` threads
+    pub fn new(thread_count: usize) -> Self {
+        Self {
+            thread_count,
+            order: Vec::new(),
+            entries: Vec::new(),
+            scratch: Vec::new(),
+            visible_count: 0,
+            incremental: false,
+        }
+    }
+
+    /// Sorts the splats `0..splat_count` back to front by the depth returned from `depth_of`, [None] culls a splat
+    ///
+    /// Returns pairs of key and splat index in the layout of the entry buffer of the renderer, without the culled splats.
+    pub fn sort(&mut self, splat_count: usize, depth_of: impl Fn(usize) -> Option<f32> + Sync) -> &[(u32, u32)] {
+        if self.order.len() != splat_count {
+            self.order.clear();
+            self.order.extend(0..splat_count as u32);
+        }
+        self.entries.clear();
+        self.entries.extend(self.order.iter().map(|splat_index| (0, *splat_index)));
+        let chunk_size = self.chunk_size();
+        map_chunks(self.entries.chunks_mut(chunk_size), |chunk| {
+            for entry in chunk {
+                entry.0 = sort_key(depth_of(entry.1 as usize));
+            }
+        });
+        // Every shift of the insertion sort costs about as much as one entry of a radix sort pass
+        self.incremental = insertion_sort(&mut self.entries, splat_count * (32 / RADIX_BITS_PER_DIGIT) as usize);
+        if !self.incremental {
+            radix_sort(&mut self.entries, &mut self.scratch, chunk_size);
+        }
+        self.order.clear();
+        self.order.extend(self.entries.iter().map(|entry| entry.1));
+        self.visible_count = self.entries.partition_point(|entry| entry.0 != CULLED_KEY);
+        &self.entries[..self.visible_count]
+    }
+
+    /// The result of the last [CpuSorter::sort]
+    pub fn entries(&self) -> &[(u32, u32)] {
+        &self.entries[..self.visible_count]
+    }
+
+    /// Whether the last [CpuSorter::sort] got away with an insertion sort instead of a full radix sort
+    pub fn was_incremental(&self) -> bool {
+        self.incremental
+    }
+
+    /// Splits the entries into one chunk per thread, unless they are too few to be worth it
+    fn chunk_size(&self) -> usize {
+        let thread_count = self.thread_count.clamp(1, self.entries.len().div_ceil(MIN_ENTRIES_PER_THREAD).max(1));
+        self.entries.len().div_ceil(thread_count).max(1)
+    }
+}
+
+/// Applies `f` to all chunks, the first one on the calling thread and each of the others on a thread of its own
+fn map_chunks<T: Send, R: Send>(mut chunks: impl Iterator<Item = T>, f: impl Fn(T) -> R + Sync) -> Vec<R> {
+    std::thread::scope(|scope| {
+        let first_chunk = chunks.next();
+        let threads: Vec<_> = chunks
+            .map(|chunk| {
+                let f = &f;
+                scope.spawn(move || f(chunk))
+            })
+            .collect();
+        first_chunk
+            .map(&f)
+            .into_iter()
+            .chain(threads.into_iter().map(|thread| thread.join().unwrap()))
+            .collect()
+    })
+}
+
+/// Stable insertion sort which gives up after `max_shifts`, returns whether it finished
+///
+/// Giving up leaves a stably sorted prefix in front of the untouched rest, so sorting stably
+/// from there yields the same result as from the original order.
+fn insertion_sort(entries: &mut [(u32, u32)], max_shifts: usize) -> bool {
+    let mut shifts = 0;
+    for index in 1..entries.len() {
+        let entry = entries[index];
+        let mut position = index;
+        while position > 0 && entries[position - 1].0 > entry.0 {
+            entries[position] = entries[position - 1];
+            position -= 1;
+        }
+        entries[position] = entry;
+        shifts += index - position;
+        if shifts > max_shifts {
+            return false;
+        }
+    }
+    true
+}
+
+/// Stable LSD radix sort of the entries by their keys, with one thread per chunk
+fn radix_sort(entries: &mut Vec<(u32, u32)>, scratch: &mut Vec<(u32, u32)>, chunk_size: usize) {
+    scratch.resize(entries.len(), (0, 0));
+    for shift in (0..32).step_by(RADIX_BITS_PER_DIGIT as usize) {
+        let digit = |entry: &(u32, u32)| (entry.0 >> shift) as usize & (RADIX_BASE - 1);
+        let histograms = map_chunks(entries.chunks(chunk_size), |chunk| {
+            let mut histogram = [0; RADIX_BASE];
+            for entry in chunk {
+                histogram[digit(entry)] += 1;
+            }
+            histogram
+        });
+        // All keys share this digit, so the pass would not change anything
+        if (0..RADIX_BASE).any(|digit| histograms.iter().map(|histogram| histogram[digit]).sum::<usize>() == entries.len()) {
+            continue;
+        }
+        // Each chunk scatters behind the same digits of all chunks before it, which keeps the sort stable
+        let mut offsets = vec![[0; RADIX_BASE]; histograms.len()];
+        let mut offset = 0;
+        for digit in 0..RADIX_BASE {
+            for (chunk_offsets, histogram) in offsets.iter_mut().zip(histograms.iter()) {
+                chunk_offsets[digit] = offset;
+                offset += histogram[digit];
+            }
+        }
+        let output = SharedOutput(scratch.as_mut_ptr());
+        map_chunks(entries.chunks(chunk_size).zip(offsets), |(chunk, mut chunk_offsets)| {
+            for entry in chunk {
+                let offset = &mut chunk_offsets[digit(entry)];
+                // Safety: the offsets of all chunks and digits partition the scratch buffer
+                unsafe { output.write(*offset, *entry) };
+                *offset += 1;
+            }
+        });
+        std::mem::swap(entries, scratch);
+    }
+}
+
+/// Entries published by a [BackgroundSorter]
+pub struct SortedEntries {
+    /// Pairs of key and splat index back to front, without the culled splats
+    pub entries: Vec<(u32, u32)>,
+    /// Number of splats of the positions the entries were sorted for
+    pub splat_count: usize,
+}
+
+/// Requests which did not reach the worker yet, newer ones replace older ones
+#[derive(Default)]
+struct Pending {
+    view: Option<SortView>,
+    positions: Option<Vec<f32>>,
+    thread_count: usize,
+    shutdown: bool,
+}
+
+struct Worker {
+    sorter: CpuSorter,
+    positions: Vec<f32>,
+}
+
+impl Worker {
+    fn sort(&mut self, view: &SortView, positions: Option<Vec<f32>>, thread_count: usize) -> SortedEntries {
+        if let Some(positions) = positions {
+            self.positions = positions;
+        }
+        let positions = &self.positions;
+        self.sorter.thread_count = thread_count;
+        let splat_count = positions.len() / 3;
+        let entries = self.sorter.sort(splat_count, |splat_index| {
+            view.model_of(splat_index)
+                .and_then(|model_index| view.depth_of(model_index, &positions[splat_index * 3..splat_index * 3 + 3]))
+        });
+        SortedEntries {
+            entries: entries.to_vec(),
+            splat_count,
+        }
+    }
+}
+
+struct Shared {
+    pending: Mutex<Pending>,
+    wake: Condvar,
+    worker: Mutex<Worker>,
+    latest: Mutex<Option<SortedEntries>>,
+}
+
+/// Sorts splats on a thread of its own, so that sorting millions of them does not stall the frame loop
+///
+/// Each [BackgroundSorter::request] replaces the one before unless the worker already started on it.
+/// Results arrive a frame or two later through [BackgroundSorter::latest]. Where threads can not be spawned,
+/// like on the web, requests are sorted right away on the calling thread instead.
+pub struct BackgroundSorter {
+    shared: Arc<Shared>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl Default for BackgroundSorter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BackgroundSorter {
+    /// Starts the worker thread
+    pub fn new() -> Self {
+        let shared = Arc::new(Shared {
+            pending: Mutex::new(Pending::default()),
+            wake: Condvar::new(),
+            worker: Mutex::new(Worker {
+                sorter: CpuSorter::new(1),
+                positions: Vec::new(),
+            }),
+            latest: Mutex::new(None),
+        });
+        let worker_shared = shared.clone();
+        let thread = std::thread::Builder::new()
+            .name("splat sorter".to_string())
+            .spawn(move || Self::work(&worker_shared))
+            .ok();
+        Self { shared, thread }
+    }
+
+    fn work(shared: &Shared) {
+        loop {
+            let (view, positions, thread_count) = {
+                let mut pending = shared.pending.lock().unwrap();
+                while pending.view.is_none() && !pending.shutdown {
+                    pending = shared.wake.wait(pending).unwrap();
+                }
+                if pending.shutdown {
+                    return;
+                }
+                (pending.view.take().unwrap(), pending.positions.take(), pending.thread_count)
+            };
+            let sorted = shared.worker.lock().unwrap().sort(&view, positions, thread_count);
+            *shared.latest.lock().unwrap() = Some(sorted);
+        }
+    }
+
+    /// Replaces the splat positions, three floats per splat in the space of their model, which all following requests refer to
+    pub fn set_positions(&self, positions: Vec<f32>) {
+        self.shared.pending.lock().unwrap().positions = Some(positions);
+    }
+
+    /// Asks for the splats to be sorted for `view`, using up to `thread_count` threads
+    pub fn request(&self, view: SortView, thread_count: usize) {
+        let mut pending = self.shared.pending.lock().unwrap();
+        if self.thread.is_none() {
+            let positions = pending.positions.take();
+            drop(pending);
+            let sorted = self.shared.worker.lock().unwrap().sort(&view, positions, thread_count);
+            *self.shared.latest.lock().unwrap() = Some(sorted);
+            return;
+        }
+        pending.view = Some(view);
+        pending.thread_count = thread_count;
+        self.shared.wake.notify_one();
+    }
+
+    /// Takes the most recent result, [None] if there is none since the last call
+    pub fn latest(&self) -> Option<SortedEntries> {
+        self.shared.latest.lock().unwrap().take()
+    }
+}
+
+impl Drop for BackgroundSorter {
+    fn drop(&mut self) {
+        self.shared.pending.lock().unwrap().shutdown = true;
+        self.shared.wake.notify_one();
+        if let Some(thread) = self.thread.take() {
+            thread.join().unwrap();
+        }
+    }
+}
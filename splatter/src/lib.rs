@@ -1,6 +1,15 @@
+pub mod cpu_renderer; // Reference implementation of shaders.wgsl on the CPU
+pub mod cpu_sorter; // Radix sort of splats by depth for DepthSorting::Cpu
 pub mod renderer;
 pub mod scene;
+pub mod shader; // Specialization of shaders.wgsl
+pub mod tiled_rasterizer; // Tile-based compute rasterizer for DepthSorting::GpuTiled
 pub mod utils;
+#[cfg(feature = "bevy")]
 pub mod bevy_plugin; // New module for Bevy integration
+#[cfg(feature = "bevy")]
 pub mod component; // New module for components
-pub mod render_plugin; // New module for rendering
\ No newline at end of file
+#[cfg(feature = "bevy")]
+pub mod render_plugin; // New module for rendering
+#[cfg(feature = "bevy")]
+pub mod asset; // Bevy asset type and loader for splat files
@@ -8,14 +8,22 @@ mod weapon;
 
 fn main() {
     App::new()
-        .add_plugins(DefaultPlugins.set(WindowPlugin {
-            primary_window: Some(Window {
-                title: "Splatter Demo".to_string(),
-                resolution: (640.0, 480.0).into(),
-                ..default()
-            }),
-            ..default()
-        }))
+        .add_plugins(
+            DefaultPlugins
+                .set(WindowPlugin {
+                    primary_window: Some(Window {
+                        title: "Splatter Demo".to_string(),
+                        resolution: (640.0, 480.0).into(),
+                        ..default()
+                    }),
+                    ..default()
+                })
+                // Reloads assets, including splat files, when they are edited
+                .set(AssetPlugin {
+                    watch_for_changes_override: Some(true),
+                    ..default()
+                }),
+        )
         .add_plugins((
             PlayerPlugin,
             WeaponPlugin,
@@ -1,25 +1,40 @@
-use std::borrow::Cow;
+use bevy::core_pipeline::core_3d::{self, CORE_3D_DEPTH_FORMAT};
+use bevy::ecs::{
+    query::QueryItem,
+    system::{lifetimeless::SRes, SystemParamItem},
+};
 use bevy::render::{
+    extract_component::ExtractComponentPlugin,
+    extract_resource::{ExtractResource, ExtractResourcePlugin},
+    render_asset::{PrepareAssetError, RenderAsset, RenderAssetPlugin, RenderAssets},
+    render_graph::{NodeRunError, RenderGraphApp, RenderGraphContext, ViewNode, ViewNodeRunner},
     render_resource::*,
-    renderer::{RenderDevice, RenderQueue},
-    render_phase::{PhaseItem, DrawFunctionId},
-    view::ViewTarget,
+    renderer::{RenderContext, RenderDevice, RenderQueue},
+    view::{ExtractedView, ViewDepthTexture, ViewTarget, VisibleEntities},
+    Extract,
+    Render,
     RenderApp,
+    RenderSet,
     ExtractSchedule,
 };
 use bevy::prelude::*;
-use crate::renderer::Configuration;
-use bevy::asset::Handle;
-use wgpu::Color;
-use std::default::Default;
-use bevy::utils::nonmax::NonMaxU32;
+use bevy::utils::HashMap;
+use std::sync::Arc;
+use crate::asset::GaussianCloud;
+use crate::component::{GaussianSplat, GaussianSplatCameraSettings, GaussianSplatSettings};
+use crate::renderer::{self, Configuration, ModelTransform, PreparedFrame};
+use geometric_algebra::{
+    ppga3d::{Point, Rotor, Translator},
+    GeometricProduct,
+};
 use crate::scene::Scene;
+use crate::shader::ShaderCache;
 
-#[derive(Resource)]
-pub struct Renderer {
-    pub config: Configuration,
-    pub pipeline: Option<RenderPipeline>,
-}
+/// Splat rendering configuration, changes are picked up by all cameras in the next frame
+///
+/// The surface format and sample count as well as the depth settings are overridden per camera.
+#[derive(Resource, ExtractResource, Clone, Default, Deref, DerefMut)]
+pub struct GaussianSplatConfiguration(pub Configuration);
 
 #[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
 struct ExtractSplatSet;
@@ -29,216 +44,326 @@ pub struct GaussianSplatRenderPlugin;
 
 impl Plugin for GaussianSplatRenderPlugin {
     fn build(&self, app: &mut App) {
+        app.init_resource::<GaussianSplatConfiguration>().add_plugins((
+            RenderAssetPlugin::<GaussianCloud>::default(),
+            ExtractComponentPlugin::<GaussianSplatCameraSettings>::default(),
+            ExtractResourcePlugin::<GaussianSplatConfiguration>::default(),
+        ));
         let render_app = app.sub_app_mut(RenderApp);
         render_app
-            .init_resource::<Renderer>()
-            .add_systems(ExtractSchedule, extract_splats.in_set(ExtractSplatSet));
+            .init_resource::<GaussianSplatViews>()
+            .add_systems(ExtractSchedule, (extract_splats, extract_camera_projections).in_set(ExtractSplatSet))
+            .add_systems(Render, prepare_splats.in_set(RenderSet::PrepareResources))
+            .add_render_graph_node::<ViewNodeRunner<GaussianSplatNode>>(core_3d::graph::NAME, GaussianSplatNode::NAME)
+            .add_render_graph_edges(
+                core_3d::graph::NAME,
+                &[
+                    core_3d::graph::node::MAIN_OPAQUE_PASS,
+                    GaussianSplatNode::NAME,
+                    core_3d::graph::node::MAIN_TRANSMISSIVE_PASS,
+                ],
+            );
     }
 }
 
-pub struct PipelineResource {
-    pub pipeline: RenderPipeline,
+/// Uploads the splats of a [GaussianCloud] once, they are kept on the GPU until the asset changes
+impl RenderAsset for GaussianCloud {
+    type ExtractedAsset = GaussianCloud;
+    type PreparedAsset = Scene;
+    type Param = SRes<RenderDevice>;
+
+    fn extract_asset(&self) -> Self::ExtractedAsset {
+        self.clone()
+    }
+
+    fn prepare_asset(
+        cloud: Self::ExtractedAsset,
+        render_device: &mut SystemParamItem<Self::Param>,
+    ) -> Result<Self::PreparedAsset, PrepareAssetError<Self::ExtractedAsset>> {
+        let mut scene = Scene::new();
+        scene.set_splats(cloud.splats);
+        scene.create_splat_buffer(render_device.wgpu_device());
+        Ok(scene)
+    }
 }
 
-pub struct GaussianSplatPhase {
-    pub entity: Entity,
-    pub pipeline: PipelineResource,
-    pub distance: f32,
-    pub draw_function: DrawFunctionId,
-    pub instance_index: usize,
+/// Render world copy of a visible [GaussianSplat] entity
+#[derive(Component, Clone)]
+pub struct ExtractedGaussianSplat {
+    pub cloud: AssetId<GaussianCloud>,
+    pub transform: GlobalTransform,
+    pub settings: GaussianSplatSettings,
 }
 
-impl Clone for GaussianSplatPhase {
-    fn clone(&self) -> Self {
-        Self {
-            entity: self.entity,
-            pipeline: PipelineResource {
-                pipeline: self.pipeline.pipeline.clone(),
-            },
-            distance: self.distance,
-            draw_function: self.draw_function,
-            instance_index: self.instance_index,
+impl ExtractedGaussianSplat {
+    /// Converts the [GlobalTransform] into a [ModelTransform], non-uniform scales are averaged
+    pub fn model_transform(&self) -> ModelTransform {
+        let (scale, rotation, translation) = self.transform.to_scale_rotation_translation();
+        let translator = Translator::new(1.0, -0.5 * translation.x, -0.5 * translation.y, -0.5 * translation.z);
+        let rotor = Rotor::new(rotation.w, -rotation.x, -rotation.y, -rotation.z);
+        ModelTransform {
+            motor: translator.geometric_product(rotor),
+            scale: (scale.x * scale.y * scale.z).abs().cbrt(),
+            splat_scale: self.settings.splat_scale,
         }
     }
 }
 
-impl PhaseItem for GaussianSplatPhase {
-    type SortKey = u64;
+/// Splat renderer of a camera and the frame it prepared
+pub struct ViewGaussianSplats {
+    pub renderer: renderer::Renderer,
+    pub frame: PreparedFrame,
+    key: ViewKey,
+}
 
-    fn entity(&self) -> Entity {
-        self.entity
-    }
+/// Everything of a camera which requires a new [renderer::Renderer] when it changes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ViewKey {
+    pub format: TextureFormat,
+    pub sample_count: u32,
+    pub settings: GaussianSplatCameraSettings,
+}
 
-    fn sort_key(&self) -> Self::SortKey {
-        0
-    }
+/// What happens to the [renderer::Renderer] of a camera when its next frame is prepared
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RendererUpdate {
+    /// The renderer stays as it is
+    Keep,
+    /// The configuration is replaced by [renderer::Renderer::set_config]
+    SetConfig,
+    /// A new renderer is created
+    Rebuild,
+}
 
-    fn draw_function(&self) -> DrawFunctionId {
-        self.draw_function
+impl ViewKey {
+    /// Overrides the per camera options of `config`
+    pub fn config(&self, config: &Configuration) -> Configuration {
+        let mut view_config = config.clone();
+        view_config.surface_configuration.format = self.format;
+        view_config.sample_count = self.sample_count;
+        view_config.depth_format = Some(CORE_3D_DEPTH_FORMAT);
+        view_config.reversed_depth = true;
+        view_config.write_depth = self.settings.write_depth;
+        view_config
     }
 
-    fn sort(items: &mut [Self]) {
-        items.sort_by_key(|item| item.sort_key());
+    /// Decides how to update a renderer which was created with `previous`, [None] if the camera has none yet
+    pub fn renderer_update(&self, previous: Option<(&ViewKey, &Configuration)>, config: &Configuration, config_changed: bool) -> RendererUpdate {
+        match previous {
+            Some((key, _)) if key == self && !config_changed => RendererUpdate::Keep,
+            Some((key, previous_config)) if key == self && previous_config.is_compatible_with(&self.config(config)) => RendererUpdate::SetConfig,
+            _ => RendererUpdate::Rebuild,
+        }
     }
+}
 
-    fn batch_range(&self) -> &std::ops::Range<u32> {
-        static RANGE: std::ops::Range<u32> = 0..0;
-        &RANGE
-    }
+/// The [ViewGaussianSplats] of all cameras, kept across frames because render world entities are not
+#[derive(Resource, Default)]
+pub struct GaussianSplatViews {
+    pub views: HashMap<Entity, ViewGaussianSplats>,
+    /// Shared by all cameras, so that each specialization of the shader is only compiled once
+    pub shader_cache: ShaderCache,
+    /// The splats visible to any camera, shared by all of them so that they are only stored and copied once
+    pub gathered_splats: Option<Arc<renderer::GatheredSplats>>,
+}
 
-    fn batch_range_mut(&mut self) -> &mut std::ops::Range<u32> {
-        panic!("batch_range_mut() should not be called directly!")
-    }
+/// Render graph node which draws the splats of a camera on top of its opaque geometry, which occludes them
+///
+/// Runs for every 3D camera, including those which render into an [Image] or only a part of their target.
+#[derive(Default)]
+pub struct GaussianSplatNode;
 
-    fn dynamic_offset(&self) -> Option<NonMaxU32> {
-        None
-    }
+impl GaussianSplatNode {
+    pub const NAME: &'static str = "gaussian_splat_pass";
+}
+
+impl ViewNode for GaussianSplatNode {
+    type ViewQuery = (&'static ExtractedView, &'static ViewTarget, &'static ViewDepthTexture);
 
-    fn dynamic_offset_mut(&mut self) -> &mut Option<NonMaxU32> {
-        panic!("dynamic_offset_mut() should not be called directly!")
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        (view, view_target, depth): QueryItem<Self::ViewQuery>,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let Some(view_splats) = world.resource::<GaussianSplatViews>().views.get(&graph.view_entity()) else {
+            return Ok(());
+        };
+        let encoder = render_context.command_encoder();
+        view_splats.renderer.sort(encoder, &view_splats.frame);
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some(Self::NAME),
+            // Keeps the meshes which were drawn before
+            color_attachments: &[Some(view_target.get_color_attachment(Operations {
+                load: LoadOp::Load,
+                store: true,
+            }))],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: &depth.view,
+                depth_ops: Some(Operations {
+                    load: LoadOp::Load,
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        });
+        let viewport = view.viewport.as_vec4();
+        render_pass.set_viewport(viewport.x, viewport.y, viewport.z, viewport.w, 0.0, 1.0);
+        view_splats.renderer.draw(&mut render_pass, &view_splats.frame);
+        Ok(())
     }
 }
 
-impl FromWorld for Renderer {
-    fn from_world(world: &mut World) -> Self {
-        let render_device = world.resource::<RenderDevice>();
-        Renderer::new(render_device, Configuration {
-            surface_configuration: wgpu::SurfaceConfiguration {
-                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-                format: wgpu::TextureFormat::Bgra8UnormSrgb,
-                width: 800,
-                height: 600,
-                present_mode: wgpu::PresentMode::Fifo,
-                alpha_mode: wgpu::CompositeAlphaMode::Auto,
-                view_formats: vec![],
+#[allow(clippy::type_complexity)]
+fn extract_splats(
+    mut commands: Commands,
+    mut previous_len: Local<usize>,
+    query: Extract<Query<(Entity, &GaussianSplat, &GlobalTransform, &ViewVisibility, Option<&GaussianSplatSettings>)>>,
+) {
+    let mut values = Vec::with_capacity(*previous_len);
+    for (entity, splat, transform, visibility, settings) in &query {
+        if !visibility.get() {
+            continue;
+        }
+        values.push((
+            entity,
+            ExtractedGaussianSplat {
+                cloud: splat.cloud.id(),
+                transform: *transform,
+                settings: settings.cloned().unwrap_or_default(),
             },
-            depth_sorting: crate::renderer::DepthSorting::Gpu,
-            use_covariance_for_scale: false,
-            use_unaligned_rectangles: false,
-            spherical_harmonics_order: 1,
-            max_splat_count: 1,
-            radix_bits_per_digit: 1,
-            frustum_culling_tolerance: 0.0,
-            ellipse_margin: 0.0,
-            splat_scale: 0.0,
-        })
+        ));
     }
+    *previous_len = values.len();
+    commands.insert_or_spawn_batch(values);
 }
 
-impl Renderer {
-    pub fn new(_render_device: &RenderDevice, config: Configuration) -> Self {
-        Self {
-            config,
-            pipeline: None,
+/// Splats are only rendered by cameras with a perspective projection
+fn extract_camera_projections(mut commands: Commands, query: Extract<Query<(Entity, &Projection), With<Camera>>>) {
+    for (entity, projection) in &query {
+        if let Projection::Perspective(projection) = projection {
+            commands.get_or_spawn(entity).insert(projection.clone());
         }
     }
+}
 
-    pub fn render_scene(
-        &mut self,
-        render_device: &RenderDevice,
-        render_queue: &RenderQueue,
-        view_target: &ViewTarget,
-        shaders: Handle<Shader>,
-    ) {
-        let shader_handle = Handle::<Shader>::weak_from_u128(123456789);
-        if !shaders.ge(&shader_handle) {
-            return;
-        }
-
-        let _shader = shader_handle.to_owned();
-
-        if self.pipeline.is_none() {
-            let bind_group_layout = render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
-                label: Some("Gaussian Splat Bind Group Layout"),
-                entries: &[],
-            });
-
-            let pipeline_layout = render_device.create_pipeline_layout(&PipelineLayoutDescriptor {
-                label: Some("Gaussian Splat Pipeline Layout"),
-                bind_group_layouts: &[&bind_group_layout],
-                push_constant_ranges: &[],
-            });
-
-            let vertex_shader_module = render_device.create_shader_module(ShaderModuleDescriptor {
-                label: Some("Vertex Shader"),
-                source: ShaderSource::Wgsl(Cow::Borrowed(include_str!("shaders.wgsl"))),
-            });
-
-            let fragment_shader_module = render_device.create_shader_module(ShaderModuleDescriptor {
-                label: Some("Fragment Shader"),
-                source: ShaderSource::Wgsl(Cow::Borrowed(include_str!("shaders.wgsl"))),
-            });
-
-            let render_pipeline_descriptor = wgpu::RenderPipelineDescriptor {
-                label: Some("Gaussian Splat Pipeline"),
-                layout: Some(&pipeline_layout),
-                vertex: wgpu::VertexState {
-                    module: &vertex_shader_module,
-                    entry_point: "main",
-                    buffers: &[],
-                },
-                fragment: Some(wgpu::FragmentState {
-                    module: &fragment_shader_module,
-                    entry_point: "main",
-                    targets: &[Some(wgpu::ColorTargetState {
-                        format: wgpu::TextureFormat::Bgra8UnormSrgb,
-                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
-                        write_mask: wgpu::ColorWrites::ALL,
-                    })],
-                }),
-                primitive: wgpu::PrimitiveState {
-                    topology: wgpu::PrimitiveTopology::PointList,
-                    strip_index_format: None,
-                    front_face: wgpu::FrontFace::Ccw,
-                    cull_mode: None,
-                    polygon_mode: wgpu::PolygonMode::Fill,
-                    unclipped_depth: false,
-                    conservative: false,
-                },
-                depth_stencil: None,
-                multisample: wgpu::MultisampleState {
-                    count: 1,
-                    mask: !0,
-                    alpha_to_coverage_enabled: false,
-                },
-                multiview: None,
-            };
-
-            let render_pipeline = render_device.create_render_pipeline(&render_pipeline_descriptor);
-            self.pipeline = Some(render_pipeline);
-        }
-
-        let texture = view_target.main_texture();
-        let texture_view = texture.create_view(&TextureViewDescriptor::default());
-        if let Some(pipeline) = &self.pipeline {
-            let mut encoder = render_device.create_command_encoder(&CommandEncoderDescriptor {
-                label: Some("Gaussian Splat Encoder"),
-            });
-
-            {
-                let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
-                    label: Some("Splat Render Pass"),
-                    color_attachments: &[Some(RenderPassColorAttachment {
-                        view: &texture_view,
-                        resolve_target: None,
-                        ops: Operations {
-                            load: LoadOp::Clear(Color::BLACK),
-                            store: true,
-                        },
-                    })],
-                    depth_stencil_attachment: None,
-                });
-
-                render_pass.set_pipeline(pipeline);
-                render_pass.draw(0..1, 0..1);
+/// Gathers the splats visible to any camera once and prepares the [renderer::Renderer] of each camera for its share of them
+///
+/// The renderer is only recreated if the camera or the [GaussianSplatConfiguration] changed in a way which affects its pipelines.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn prepare_splats(
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    config: Res<GaussianSplatConfiguration>,
+    msaa: Res<Msaa>,
+    clouds: Res<RenderAssets<GaussianCloud>>,
+    splats: Query<&ExtractedGaussianSplat>,
+    views: Query<(
+        Entity,
+        &ExtractedView,
+        &ViewTarget,
+        &VisibleEntities,
+        &PerspectiveProjection,
+        Option<&GaussianSplatCameraSettings>,
+    )>,
+    splat_views: ResMut<GaussianSplatViews>,
+) {
+    let splat_views = splat_views.into_inner();
+    splat_views.views.retain(|entity, _| views.contains(*entity));
+    // The renderers would panic on an invalid configuration, so the cameras keep their previous ones until it is fixed
+    let config_error = config.validate().err();
+    if let Some(error) = config_error.filter(|_| config.is_changed()) {
+        error!("Keeping the previous splat renderers: {}", error);
+    }
+    if config_error.is_none()
+        && splat_views
+            .gathered_splats
+            .as_ref()
+            .is_none_or(|gathered_splats| gathered_splats.max_splat_count() != config.max_splat_count)
+    {
+        splat_views.gathered_splats = Some(Arc::new(renderer::GatheredSplats::new(
+            render_device.wgpu_device(),
+            config.max_splat_count,
+        )));
+        // The renderers are bound to the previous buffer
+        splat_views.views.clear();
+    }
+    let Some(gathered_splats) = splat_views.gathered_splats.clone() else {
+        return;
+    };
+    let scenes_of = |visible_entities: &VisibleEntities| {
+        visible_entities
+            .entities
+            .iter()
+            .filter_map(|entity| splats.get(*entity).ok())
+            .filter_map(|splat| clouds.get(splat.cloud).map(|scene| (scene, splat.model_transform())))
+            .collect::<Vec<_>>()
+    };
+    let all_scenes = views
+        .iter()
+        .flat_map(|(_, _, _, visible_entities, _, _)| scenes_of(visible_entities))
+        .map(|(scene, _)| scene)
+        .collect::<Vec<_>>();
+    gathered_splats.gather(render_device.wgpu_device(), &render_queue, &all_scenes);
+    for (entity, view, view_target, visible_entities, projection, settings) in &views {
+        let scenes = scenes_of(visible_entities);
+        let key = ViewKey {
+            format: view_target.main_texture_format(),
+            sample_count: msaa.samples(),
+            settings: settings.copied().unwrap_or_default(),
+        };
+        let update = if config_error.is_some() {
+            RendererUpdate::Keep
+        } else {
+            let previous = splat_views
+                .views
+                .get(&entity)
+                .map(|view_splats| (&view_splats.key, view_splats.renderer.config()));
+            key.renderer_update(previous, &config, config.is_changed())
+        };
+        match update {
+            RendererUpdate::Keep => {}
+            RendererUpdate::SetConfig => {
+                let view_splats = splat_views.views.get_mut(&entity).unwrap();
+                view_splats.renderer.set_config(key.config(&config));
+            }
+            RendererUpdate::Rebuild => {
+                splat_views.views.insert(
+                    entity,
+                    ViewGaussianSplats {
+                        renderer: renderer::Renderer::with_gathered_splats(
+                            render_device.wgpu_device(),
+                            key.config(&config),
+                            &mut splat_views.shader_cache,
+                            gathered_splats.clone(),
+                        ),
+                        frame: PreparedFrame::default(),
+                        key,
+                    },
+                );
             }
-
-            render_queue.submit(std::iter::once(encoder.finish()));
         }
+        let Some(view_splats) = splat_views.views.get_mut(&entity) else {
+            continue;
+        };
+        let viewport_size = Extent3d {
+            width: view.viewport.z.max(1),
+            height: view.viewport.w.max(1),
+            depth_or_array_layers: 1,
+        };
+        let view_height = (projection.fov * 0.5).tan();
+        let camera = renderer::Camera {
+            // Bevy cameras look along their negative Z axis
+            matrix: (view.transform.compute_matrix() * Mat4::from_scale(Vec3::new(1.0, 1.0, -1.0)))
+                .to_cols_array_2d()
+                .map(|[x, y, z, w]| Point::new(x, y, z, w)),
+            view_size: [view_height * viewport_size.width as f32 / viewport_size.height as f32, view_height],
+            near: projection.near,
+            far: projection.far,
+        };
+        view_splats.frame = view_splats
+            .renderer
+            .prepare_frame(render_device.wgpu_device(), &render_queue, viewport_size, &camera, &scenes);
     }
-}
-
-fn extract_splats(_commands: Commands, _scene: Res<Scene>) {
-    // Extract splats from the scene
-    // This is a placeholder for the actual extraction logic
 }
\ No newline at end of file
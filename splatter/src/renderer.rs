@@ -1,35 +1,62 @@
-use std::convert::TryInto;
 use crate::{
-    scene::{Scene, Splat},
-    utils::{mat4_multiplication, mat4_transform, motor3d_to_mat4, perspective_projection, transmute_slice},
+    cpu_sorter::{BackgroundSorter, CpuSorter, SortView},
+    scene::{GpuSplat, Scene},
+    shader::{ShaderCache, SortingLayout},
+    tiled_rasterizer::{TiledRasterizer, TiledTargets},
+    utils::{mat4_multiplication, mat4_orthonormal_inverse, motor3d_to_mat4, perspective_projection, transmute_slice},
 };
 use geometric_algebra::{
     ppga3d::{Motor, Point},
-    Inverse,
+    One,
 };
+use std::sync::Arc;
 use wgpu::util::DeviceExt;
-use bevy::prelude::*;
-use bevy::render::render_resource::*;
-use bevy::render::renderer::RenderDevice;
-use wgpu::Queue;
+
 /// Selects how splats are sorted by their distance to the camera
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DepthSorting {
     /// No sorting at all
     None,
     /// Sorting takes place on the CPU and is copied over to the GPU
     Cpu,
+    /// Like [DepthSorting::Cpu] but on a background thread, so the sorted order lags a frame or two behind the camera
+    ///
+    /// Nothing is drawn until the first sorted order of the current splats arrives.
+    CpuBackground,
     /// Sorting takes place internally on the GPU
     Gpu,
     /// Like [DepthSorting::Gpu] and additionally skips rendering frustum culled splats by stream compaction
     GpuIndirectDraw,
+    /// Skips the rasterizer and instead bins the splats into 16x16 pixel tiles, sorts each tile and composites it front to back in compute shaders
+    ///
+    /// Stops compositing pixels once they are opaque, which pays off with heavy overdraw.
+    /// Splats are neither tested against the depth buffer nor written into it, see [Configuration::write_depth].
+    GpuTiled,
 }
 
 /// Rendering configuration
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Configuration {
     /// Format of the frame buffer texture
     pub surface_configuration: wgpu::SurfaceConfiguration,
+    /// Number of samples per pixel of the frame buffer texture, 1 unless it is multisampled
+    pub sample_count: u32,
+    /// Format of the depth buffer which splats are tested against, [None] if there is no depth buffer
+    ///
+    /// [DepthSorting::GpuTiled] ignores the depth buffer, it only has to match the depth attachment of the render pass.
+    pub depth_format: Option<wgpu::TextureFormat>,
+    /// Maps the near plane to depth 1 and infinity to depth 0 like Bevy does, instead of the near plane to 0 and the far plane to 1
+    pub reversed_depth: bool,
+    /// Writes the depth of dense splats in an extra pass, so that geometry drawn afterwards is occluded by them. Requires a [Configuration::depth_format]
+    ///
+    /// Does nothing with [DepthSorting::GpuTiled], which does not rasterize the splats in the render pass.
+    pub write_depth: bool,
     /// Selects how splats are sorted by their distance to the camera
     pub depth_sorting: DepthSorting,
+    /// Number of threads [DepthSorting::Cpu] and [DepthSorting::CpuBackground] sort with, 1 does not spawn any extra
+    pub cpu_sorting_thread_count: usize,
     /// Uses the parallel projected covariance for decomposition of semi axes
     pub use_covariance_for_scale: bool,
     /// Decomposes the conic sections and renders them as rotated rectangles
@@ -38,7 +65,7 @@ pub struct Configuration {
     pub spherical_harmonics_order: usize,
     /// Maximum number of splats to allocate memory for
     pub max_splat_count: usize,
-    /// How many bits of the key to bin in a single pass. Should be 8
+    /// How many bits of the key to bin in a single pass, one of 1, 2, 4 or 8. Should be 8
     pub radix_bits_per_digit: usize,
     /// Factor by which the center of a splat can be outside the frustum without being called. Should be > 1.0
     pub frustum_culling_tolerance: f32,
@@ -48,6 +75,87 @@ pub struct Configuration {
     pub splat_scale: f32,
 }
 
+impl Default for Configuration {
+    fn default() -> Self {
+        Self {
+            surface_configuration: wgpu::SurfaceConfiguration {
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                format: wgpu::TextureFormat::Bgra8UnormSrgb,
+                width: 800,
+                height: 600,
+                present_mode: wgpu::PresentMode::Fifo,
+                alpha_mode: wgpu::CompositeAlphaMode::Auto,
+                view_formats: vec![],
+            },
+            sample_count: 1,
+            depth_format: None,
+            reversed_depth: false,
+            write_depth: false,
+            depth_sorting: DepthSorting::Gpu,
+            cpu_sorting_thread_count: std::thread::available_parallelism().map_or(1, usize::from),
+            use_covariance_for_scale: true,
+            use_unaligned_rectangles: true,
+            spherical_harmonics_order: 3,
+            // Keeps the gathered splats within the default storage buffer binding size of 128 MiB
+            max_splat_count: 1024 * 512,
+            radix_bits_per_digit: 8,
+            frustum_culling_tolerance: 1.1,
+            ellipse_margin: 2.0,
+            splat_scale: 1.0,
+        }
+    }
+}
+
+/// Reasons why a [Renderer] can not be created with a [Configuration]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfigurationError {
+    /// [Configuration::radix_bits_per_digit] is not 1, 2, 4 or 8
+    UnsupportedRadixBitsPerDigit(usize),
+}
+
+impl std::fmt::Display for ConfigurationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnsupportedRadixBitsPerDigit(radix_bits_per_digit) => {
+                write!(f, "radix_bits_per_digit has to be 1, 2, 4 or 8, not {}", radix_bits_per_digit)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigurationError {}
+
+impl Configuration {
+    /// Checks everything [Renderer::new] would panic on, so that configurations edited at runtime can be rejected instead
+    pub fn validate(&self) -> Result<(), ConfigurationError> {
+        // The digits have to cover the keys exactly, in an even number of passes so that the sorted entries end up in entry_buffer_a.
+        // More bits exceed the workgroup size of any device
+        if !matches!(self.radix_bits_per_digit, 1 | 2 | 4 | 8) {
+            return Err(ConfigurationError::UnsupportedRadixBitsPerDigit(self.radix_bits_per_digit));
+        }
+        Ok(())
+    }
+
+    /// Checks whether a [Renderer] created with `self` can switch to `other`
+    ///
+    /// Only [Configuration::frustum_culling_tolerance], [Configuration::ellipse_margin], [Configuration::splat_scale],
+    /// [Configuration::cpu_sorting_thread_count] and the size of the surface may differ,
+    /// everything else is baked into the pipelines and buffers.
+    pub fn is_compatible_with(&self, other: &Self) -> bool {
+        self.surface_configuration.format == other.surface_configuration.format
+            && self.sample_count == other.sample_count
+            && self.depth_format == other.depth_format
+            && self.reversed_depth == other.reversed_depth
+            && self.write_depth == other.write_depth
+            && self.depth_sorting == other.depth_sorting
+            && self.use_covariance_for_scale == other.use_covariance_for_scale
+            && self.use_unaligned_rectangles == other.use_unaligned_rectangles
+            && self.spherical_harmonics_order == other.spherical_harmonics_order
+            && self.max_splat_count == other.max_splat_count
+            && self.radix_bits_per_digit == other.radix_bits_per_digit
+    }
+}
+
 #[repr(C)]
 pub(crate) struct Uniforms {
     camera_matrix: [Point; 4],
@@ -59,148 +167,888 @@ pub(crate) struct Uniforms {
     ellipse_size_bias: f32,
     ellipse_margin: f32,
     splat_scale: f32,
-    padding: [f32; 0],
+    splat_count: u32,
+    model_count: u32,
+    depth_projection: [f32; 2],
+}
+
+/// Maximum number of scenes which can be rendered in one frame
+pub const MAX_MODEL_COUNT: usize = 256;
+
+/// Placement of a [Scene] in the world
+///
+/// The scale is uniform, so that the splats remain ellipsoids.
+#[derive(Clone, Copy)]
+pub struct ModelTransform {
+    /// Rotation and translation
+    pub motor: Motor,
+    pub scale: f32,
+    /// Factor to scale the splat ellipsoids of this scene with, in addition to [Configuration::splat_scale]
+    pub splat_scale: f32,
+}
+
+impl Default for ModelTransform {
+    fn default() -> Self {
+        Self {
+            motor: Motor::one(),
+            scale: 1.0,
+            splat_scale: 1.0,
+        }
+    }
+}
+
+impl ModelTransform {
+    /// 4x4 matrix which transforms from model space to world space
+    pub fn matrix(&self) -> [Point; 4] {
+        let mut matrix = motor3d_to_mat4(&self.motor);
+        for column in &mut matrix[0..3] {
+            *column *= self.scale;
+        }
+        matrix
+    }
+}
+
+#[repr(C)]
+pub(crate) struct Model {
+    transform: [Point; 4],
+    scale: f32,
+    splat_offset: u32,
+    splat_scale: f32,
+    /// Where the splats of the scene start in [GatheredSplats]
+    buffer_offset: u32,
+}
+
+/// View and projection of a frame, the camera looks along the positive Z axis of its local space
+#[derive(Clone, Copy)]
+pub struct Camera {
+    /// Transforms from camera space to world space, the upper 3x3 part has to be orthonormal
+    pub matrix: [Point; 4],
+    /// Tangents of half the horizontal and vertical field of view
+    pub view_size: [f32; 2],
+    pub near: f32,
+    pub far: f32,
+}
+
+impl Camera {
+    /// Perspective camera placed by `motor` with a vertical field of view of 90 degrees
+    pub fn from_motor(motor: Motor, viewport_size: wgpu::Extent3d) -> Self {
+        let field_of_view_y = std::f32::consts::PI * 0.5;
+        let view_height = (field_of_view_y * 0.5).tan();
+        let view_width = (viewport_size.width as f32 / viewport_size.height as f32) / view_height;
+        Self {
+            matrix: motor3d_to_mat4(&motor),
+            view_size: [view_width, view_height],
+            near: 1.0,
+            far: 1000.0,
+        }
+    }
+}
+
+/// Splat counts of a frame, returned by [Renderer::prepare_frame]
+#[derive(Clone, Debug, Default)]
+pub struct PreparedFrame {
+    /// Number of splats gathered from all scenes
+    pub splat_count: usize,
+    /// Number of splats to draw, unless [DepthSorting::GpuIndirectDraw] decides that on the GPU
+    pub instance_count: usize,
+    /// Buffers of the viewport size for [DepthSorting::GpuTiled]
+    pub(crate) tiled_targets: Option<Arc<TiledTargets>>,
+}
+
+/// The splats of several scenes in one GPU buffer, which the [Renderer]s of different views can share
+///
+/// Each scene is stored once, no matter how often it is placed.
+pub struct GatheredSplats {
+    max_splat_count: usize,
+    splat_buffer: wgpu::Buffer,
+    /// [Scene::revision] and offset in `splat_buffer` of each gathered scene, [None] for those which did not fit
+    scenes: std::sync::Mutex<Vec<(u64, Option<usize>)>>,
+}
+
+impl GatheredSplats {
+    /// Allocates room for `max_splat_count` splats
+    pub fn new(device: &wgpu::Device, max_splat_count: usize) -> Self {
+        Self {
+            max_splat_count,
+            splat_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Gathered Splat Buffer"),
+                size: (max_splat_count * std::mem::size_of::<GpuSplat>()) as u64,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            scenes: std::sync::Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn max_splat_count(&self) -> usize {
+        self.max_splat_count
+    }
+
+    /// Copies the splats of `scenes` into the buffer, unless all of them are in there already
+    ///
+    /// Otherwise the buffer is refilled with `scenes` alone, skipping those without a [Scene::splat_buffer] and those which do not fit.
+    /// Scenes which keep their offset are not copied again. Renderers sharing the buffer need the union of their scenes gathered up front.
+    pub fn gather(&self, device: &wgpu::Device, queue: &wgpu::Queue, scenes: &[&Scene]) {
+        let mut gathered_scenes = self.scenes.lock().unwrap();
+        let is_gathered =
+            |gathered_scenes: &[(u64, Option<usize>)], scene: &Scene| gathered_scenes.iter().any(|(revision, _)| *revision == scene.revision());
+        if scenes
+            .iter()
+            .all(|scene| scene.splat_buffer.is_none() || is_gathered(&gathered_scenes, scene))
+        {
+            return;
+        }
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        let mut layout = Vec::with_capacity(scenes.len());
+        let mut splat_count = 0;
+        for scene in scenes {
+            let Some(scene_splat_buffer) = &scene.splat_buffer else {
+                continue;
+            };
+            if is_gathered(&layout, scene) {
+                continue;
+            }
+            let offset = (splat_count + scene.splat_count <= self.max_splat_count).then_some(splat_count);
+            if let Some(offset) = offset {
+                if !gathered_scenes.contains(&(scene.revision(), Some(offset))) {
+                    let splat_size = std::mem::size_of::<GpuSplat>() as u64;
+                    encoder.copy_buffer_to_buffer(
+                        scene_splat_buffer,
+                        0,
+                        &self.splat_buffer,
+                        offset as u64 * splat_size,
+                        scene.splat_count as u64 * splat_size,
+                    );
+                }
+                splat_count += scene.splat_count;
+            }
+            layout.push((scene.revision(), offset));
+        }
+        queue.submit(Some(encoder.finish()));
+        *gathered_scenes = layout;
+    }
+
+    /// Whether the current splats of `scene` are in the buffer
+    pub fn contains(&self, scene: &Scene) -> bool {
+        Self::offset_of(&self.scenes.lock().unwrap(), scene).is_some()
+    }
+
+    /// Offset of the splats of `scene` in the buffer, [None] if it was not gathered
+    fn offset_of(gathered_scenes: &[(u64, Option<usize>)], scene: &Scene) -> Option<usize> {
+        gathered_scenes
+            .iter()
+            .find(|(revision, _)| *revision == scene.revision())
+            .and_then(|(_, offset)| *offset)
+    }
 }
 
 /// Splats forward renderer
 pub struct Renderer {
-    pipeline: RenderPipeline,
-    bind_group_layout: BindGroupLayout,
-    vertex_buffer: Buffer,
+    config: Configuration,
+    radix_digit_places: usize,
+    radix_base: usize,
+    workgroup_entries_a: usize,
+    workgroup_entries_c: usize,
+    max_tile_count_c: usize,
+    sorting_buffer_size: usize,
+    radix_sort_a_pipeline: wgpu::ComputePipeline,
+    radix_sort_b_pipeline: wgpu::ComputePipeline,
+    radix_sort_c_pipeline: wgpu::ComputePipeline,
+    pipeline: wgpu::RenderPipeline,
+    depth_pipeline: Option<wgpu::RenderPipeline>,
+    uniform_buffer: wgpu::Buffer,
+    sorting_buffer: wgpu::Buffer,
+    /// Sorted entries are read from here, radix sort ping-pongs between this and `entry_buffer_b`
+    entry_buffer_a: wgpu::Buffer,
+    /// The splats of all scenes of a frame, gathered so that they can be sorted together
+    gathered_splats: Arc<GatheredSplats>,
+    model_buffer: wgpu::Buffer,
+    /// One per radix sort pass, with the input and output entry buffers swapped each time
+    compute_bind_groups: Vec<wgpu::BindGroup>,
+    render_bind_group: wgpu::BindGroup,
+    /// Keeps its buffers and the order of the previous frame for [DepthSorting::Cpu]
+    cpu_sorter: std::sync::Mutex<CpuSorter>,
+    /// Started by the first frame with [DepthSorting::CpuBackground]
+    background_sorting: std::sync::Mutex<Option<BackgroundSorting>>,
+    /// Only exists for [DepthSorting::GpuTiled]
+    tiled_rasterizer: Option<TiledRasterizer>,
+}
+
+/// State of [DepthSorting::CpuBackground] which persists across frames
+struct BackgroundSorting {
+    sorter: BackgroundSorter,
+    /// [Scene::revision] of each scene whose positions the sorter has
+    scene_revisions: Vec<u64>,
+    /// Splat count the entries in `entry_buffer_a` were sorted for
+    uploaded_splat_count: usize,
+    uploaded_instance_count: usize,
 }
 
 impl Renderer {
     /// Constructs a new [Renderer]
-    pub fn new(device: &RenderDevice) -> Self {
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Splat Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/splat.wgsl").into()),
-        });
+    pub fn new(device: &wgpu::Device, config: Configuration) -> Self {
+        Self::with_shader_cache(device, config, &mut ShaderCache::default())
+    }
+
+    /// Constructs a new [Renderer], reusing the shader of a previous one with the same specialization
+    pub fn with_shader_cache(device: &wgpu::Device, config: Configuration, shader_cache: &mut ShaderCache) -> Self {
+        let gathered_splats = Arc::new(GatheredSplats::new(device, config.max_splat_count));
+        Self::with_gathered_splats(device, config, shader_cache, gathered_splats)
+    }
+
+    /// Constructs a new [Renderer] which reads the splats from `gathered_splats`, so that other renderers can share them
+    ///
+    /// Call [GatheredSplats::gather] with the scenes of all sharing renderers before preparing their frames.
+    pub fn with_gathered_splats(
+        device: &wgpu::Device,
+        config: Configuration,
+        shader_cache: &mut ShaderCache,
+        gathered_splats: Arc<GatheredSplats>,
+    ) -> Self {
+        let shader = shader_cache.get(device, &config);
 
-        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        let sorting_layout = SortingLayout::new(&config);
+
+        let storage_binding = |binding: u32, visibility: wgpu::ShaderStages, read_only: bool| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let uniform_binding = |binding: u32, visibility: wgpu::ShaderStages| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let compute_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Sorting Bind Group Layout"),
+            entries: &[
+                uniform_binding(0, wgpu::ShaderStages::COMPUTE),
+                uniform_binding(1, wgpu::ShaderStages::COMPUTE),
+                storage_binding(2, wgpu::ShaderStages::COMPUTE, false),
+                storage_binding(3, wgpu::ShaderStages::COMPUTE, false),
+                storage_binding(4, wgpu::ShaderStages::COMPUTE, false),
+                storage_binding(6, wgpu::ShaderStages::COMPUTE, true),
+                storage_binding(7, wgpu::ShaderStages::COMPUTE, true),
+            ],
+        });
+        // Binding 5 aliases the entries written by the sorting passes, so rendering gets a layout of its own
+        let render_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("Splat Bind Group Layout"),
-            entries: &[],
+            entries: &[
+                uniform_binding(0, wgpu::ShaderStages::VERTEX_FRAGMENT),
+                storage_binding(5, wgpu::ShaderStages::VERTEX, true),
+                storage_binding(6, wgpu::ShaderStages::VERTEX, true),
+                storage_binding(7, wgpu::ShaderStages::VERTEX, true),
+            ],
+        });
+
+        let compute_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Sorting Pipeline Layout"),
+            bind_group_layouts: &[&compute_bind_group_layout],
+            push_constant_ranges: &[],
         });
+        let create_compute_pipeline = |label: &str, entry_point: &str| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&compute_pipeline_layout),
+                module: &shader,
+                entry_point,
+            })
+        };
+        let radix_sort_a_pipeline = create_compute_pipeline("Radix Sort A Pipeline", "radixSortA");
+        let radix_sort_b_pipeline = create_compute_pipeline("Radix Sort B Pipeline", "radixSortB");
+        let radix_sort_c_pipeline = create_compute_pipeline("Radix Sort C Pipeline", "radixSortC");
 
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("Splat Pipeline Layout"),
-            bind_group_layouts: &[&bind_group_layout],
+            bind_group_layouts: &[&render_bind_group_layout],
             push_constant_ranges: &[],
         });
 
-        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Splat Pipeline"),
-            layout: Some(&pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                buffers: &[],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: TextureFormat::Bgra8UnormSrgb,
-                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                unclipped_depth: false,
-                polygon_mode: wgpu::PolygonMode::Fill,
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
+        let depth_compare = if config.reversed_depth {
+            wgpu::CompareFunction::GreaterEqual
+        } else {
+            wgpu::CompareFunction::LessEqual
+        };
+        let create_render_pipeline = |label: &str, fragment_entry_point: &str, color_target: wgpu::ColorTargetState, depth_write_enabled: bool| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vertex",
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: fragment_entry_point,
+                    targets: &[Some(color_target)],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleStrip,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: None,
+                    unclipped_depth: false,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    conservative: false,
+                },
+                depth_stencil: config.depth_format.map(|format| wgpu::DepthStencilState {
+                    format,
+                    depth_write_enabled,
+                    depth_compare,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: config.sample_count,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None,
+            })
+        };
+
+        // Splats are occluded by opaque geometry, but do not occlude each other. They are drawn back to front with premultiplied alpha
+        let pipeline = create_render_pipeline(
+            "Splat Pipeline",
+            "fragment",
+            wgpu::ColorTargetState {
+                format: config.surface_configuration.format,
+                blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
             },
-            multiview: None,
+            false,
+        );
+
+        // Only writes depth after all splats were blended, so that geometry drawn afterwards is occluded
+        let depth_pipeline = if config.write_depth && config.depth_format.is_some() && config.depth_sorting != DepthSorting::GpuTiled {
+            Some(create_render_pipeline(
+                "Splat Depth Pipeline",
+                "depth_fragment",
+                wgpu::ColorTargetState {
+                    format: config.surface_configuration.format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::empty(),
+                },
+                true,
+            ))
+        } else {
+            None
+        };
+
+        let tiled_rasterizer = (config.depth_sorting == DepthSorting::GpuTiled)
+            .then(|| TiledRasterizer::new(device, &config, &shader, &compute_bind_group_layout, &render_bind_group_layout));
+
+        let model_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Model Buffer"),
+            size: (MAX_MODEL_COUNT * std::mem::size_of::<Model>()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Uniform Buffer"),
+            size: std::mem::size_of::<Uniforms>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
         });
 
-        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Splat Vertex Buffer"),
-            size: 1024,
-            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        let sorting_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Sorting Buffer"),
+            size: sorting_layout.sorting_buffer_size as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::INDIRECT,
             mapped_at_creation: false,
         });
 
+        // Radix sort C reads whole tiles, so the entry buffers are rounded up to them
+        let entry_buffer_size = (sorting_layout.max_tile_count_c * sorting_layout.workgroup_entries_c * std::mem::size_of::<(u32, u32)>()) as u64;
+        let entry_buffer_a = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Entry Buffer A"),
+            size: entry_buffer_size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let entry_buffer_b = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Entry Buffer B"),
+            size: entry_buffer_size,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        // SortingLayout ensures an even number of passes, which leaves the sorted entries in entry_buffer_a.
+        // Radix sort A writes into it via the bind group of pass 1
+        let compute_bind_groups = (0..sorting_layout.radix_digit_places)
+            .map(|pass_index| {
+                let sorting_pass_index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Sorting Pass Index Buffer"),
+                    contents: transmute_slice::<_, u8>(&[pass_index as u32]),
+                    usage: wgpu::BufferUsages::UNIFORM,
+                });
+                let (input_entries, output_entries) = if pass_index % 2 == 0 {
+                    (&entry_buffer_a, &entry_buffer_b)
+                } else {
+                    (&entry_buffer_b, &entry_buffer_a)
+                };
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Sorting Bind Group"),
+                    layout: &compute_bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: uniform_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: sorting_pass_index_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: sorting_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 3,
+                            resource: input_entries.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 4,
+                            resource: output_entries.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 6,
+                            resource: gathered_splats.splat_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 7,
+                            resource: model_buffer.as_entire_binding(),
+                        },
+                    ],
+                })
+            })
+            .collect();
+
+        let render_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Splat Bind Group"),
+            layout: &render_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: entry_buffer_a.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: gathered_splats.splat_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 7,
+                    resource: model_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
         Self {
+            config,
+            radix_digit_places: sorting_layout.radix_digit_places,
+            radix_base: sorting_layout.radix_base,
+            workgroup_entries_a: sorting_layout.workgroup_entries_a,
+            workgroup_entries_c: sorting_layout.workgroup_entries_c,
+            max_tile_count_c: sorting_layout.max_tile_count_c,
+            sorting_buffer_size: sorting_layout.sorting_buffer_size,
+            radix_sort_a_pipeline,
+            radix_sort_b_pipeline,
+            radix_sort_c_pipeline,
             pipeline,
-            bind_group_layout,
-            vertex_buffer,
+            depth_pipeline,
+            uniform_buffer,
+            sorting_buffer,
+            entry_buffer_a,
+            gathered_splats,
+            model_buffer,
+            compute_bind_groups,
+            render_bind_group,
+            cpu_sorter: std::sync::Mutex::new(CpuSorter::default()),
+            background_sorting: std::sync::Mutex::new(None),
+            tiled_rasterizer,
+        }
+    }
+
+    /// The configuration which is currently in use
+    pub fn config(&self) -> &Configuration {
+        &self.config
+    }
+
+    /// Replaces the configuration without recreating any pipelines or buffers
+    ///
+    /// Returns `false` and keeps the current configuration if `config` is not [compatible](Configuration::is_compatible_with).
+    pub fn set_config(&mut self, config: Configuration) -> bool {
+        if !self.config.is_compatible_with(&config) {
+            return false;
         }
+        self.config = config;
+        true
     }
 
-    /// Renders the given `scene` into `frame_view`
+    /// Renders the given `scenes` into `frame_view`, the splats of all scenes are sorted together
+    ///
+    /// Scenes without a [Scene::splat_buffer] are skipped, as are those which exceed
+    /// [Configuration::max_splat_count] or [MAX_MODEL_COUNT].
+    /// `depth_view` is required exactly if there is a [Configuration::depth_format], its content is tested against and kept.
+    #[allow(clippy::too_many_arguments)]
     pub fn render_frame(
         &self,
-        device: &RenderDevice,
-        queue: &Queue,
-        frame_view: &TextureView,
-        viewport_size: Extent3d,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        frame_view: &wgpu::TextureView,
+        depth_view: Option<&wgpu::TextureView>,
+        viewport_size: wgpu::Extent3d,
         camera_motor: Motor,
-        scene: &Scene,
+        scenes: &[(&Scene, ModelTransform)],
     ) {
-        let camera_matrix = motor3d_to_mat4(&camera_motor);
-        let view_matrix = motor3d_to_mat4(&camera_motor.inverse());
-        let field_of_view_y = std::f32::consts::PI * 0.5;
-        let view_height = (field_of_view_y * 0.5).tan();
-        let view_width = (viewport_size.width as f32 / viewport_size.height as f32) / view_height;
-        let projection_matrix = perspective_projection(view_width, view_height, 1.0, 1000.0);
+        assert_eq!(
+            depth_view.is_some(),
+            self.config.depth_format.is_some(),
+            "render_frame needs a depth view exactly if the configuration has a depth format"
+        );
+        let frame = self.prepare_frame(device, queue, viewport_size, &Camera::from_motor(camera_motor, viewport_size), scenes);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        self.sort(&mut encoder, &frame);
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: frame_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: depth_view.map(|view| wgpu::RenderPassDepthStencilAttachment {
+                    view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    }),
+                    stencil_ops: None,
+                }),
+            });
+            self.draw(&mut render_pass, &frame);
+        }
+        queue.submit(Some(encoder.finish()));
+    }
+
+    /// Renders `scene` into a new image of `width` x `height` pixels, without needing a window or surface
+    ///
+    /// The scene needs a [Scene::splat_buffer] and pixels which no splat covers stay transparent. Blocks until the GPU is done.
+    /// Panics unless [Configuration::surface_configuration] has an 8 bit RGBA or BGRA format.
+    #[cfg(feature = "image")]
+    pub fn render_to_image(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        scene: &Scene,
+        camera: &Camera,
+        width: u32,
+        height: u32,
+    ) -> image::RgbaImage {
+        self.render_scenes_to_image(device, queue, &[(scene, ModelTransform::default())], camera, width, height)
+    }
+
+    /// Like [Renderer::render_to_image] but with several placed `scenes`, whose splats are sorted together
+    #[cfg(feature = "image")]
+    pub fn render_scenes_to_image(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        scenes: &[(&Scene, ModelTransform)],
+        camera: &Camera,
+        width: u32,
+        height: u32,
+    ) -> image::RgbaImage {
+        let format = self.config.surface_configuration.format;
+        let swap_red_and_blue = match format {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+            _ => panic!("{:?} can not be read back into an RGBA image", format),
+        };
+        let viewport_size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let create_texture = |label: &str, format: wgpu::TextureFormat, sample_count: u32, usage: wgpu::TextureUsages| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: viewport_size,
+                mip_level_count: 1,
+                sample_count,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage,
+                view_formats: &[],
+            })
+        };
+        let target_texture = create_texture(
+            "Offscreen Target",
+            format,
+            1,
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        );
+        let target_view = target_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        // Multisampled frames are resolved into the target texture
+        let multisampled_view = (self.config.sample_count > 1).then(|| {
+            create_texture(
+                "Offscreen Multisampled Target",
+                format,
+                self.config.sample_count,
+                wgpu::TextureUsages::RENDER_ATTACHMENT,
+            )
+            .create_view(&wgpu::TextureViewDescriptor::default())
+        });
+        let depth_view = self.config.depth_format.map(|depth_format| {
+            create_texture(
+                "Offscreen Depth",
+                depth_format,
+                self.config.sample_count,
+                wgpu::TextureUsages::RENDER_ATTACHMENT,
+            )
+            .create_view(&wgpu::TextureViewDescriptor::default())
+        });
+        // Rows of texture to buffer copies have to be aligned, the padding is stripped again below
+        let unpadded_bytes_per_row = width * 4;
+        let padded_bytes_per_row = unpadded_bytes_per_row.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Offscreen Readback Buffer"),
+            size: padded_bytes_per_row as u64 * height as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let frame = self.prepare_frame(device, queue, viewport_size, camera, scenes);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        self.sort(&mut encoder, &frame);
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: multisampled_view.as_ref().unwrap_or(&target_view),
+                    resolve_target: multisampled_view.as_ref().map(|_| &target_view),
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: depth_view.as_ref().map(|view| wgpu::RenderPassDepthStencilAttachment {
+                    view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(if self.config.reversed_depth { 0.0 } else { 1.0 }),
+                        store: false,
+                    }),
+                    stencil_ops: None,
+                }),
+            });
+            self.draw(&mut render_pass, &frame);
+        }
+        encoder.copy_texture_to_buffer(
+            target_texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &readback_buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            viewport_size,
+        );
+        queue.submit(Some(encoder.finish()));
+
+        let buffer_slice = readback_buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        buffer_slice.map_async(wgpu::MapMode::Read, move |result| sender.send(result).unwrap());
+        device.poll(wgpu::Maintain::Wait);
+        receiver.recv().unwrap().expect("Failed to map the offscreen readback buffer");
+        let mut pixels = Vec::with_capacity((unpadded_bytes_per_row * height) as usize);
+        for row in buffer_slice.get_mapped_range().chunks_exact(padded_bytes_per_row as usize) {
+            pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+        }
+        readback_buffer.unmap();
+        // The tiled rasterizer only learns how many tile entries a frame needs after rendering it
+        if self
+            .tiled_rasterizer
+            .as_ref()
+            .is_some_and(|tiled_rasterizer| tiled_rasterizer.grow_tile_entries(device, true))
+        {
+            return self.render_scenes_to_image(device, queue, scenes, camera, width, height);
+        }
+        if swap_red_and_blue {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+        image::RgbaImage::from_raw(width, height, pixels).unwrap()
+    }
+
+    /// Gathers the splats of all `scenes` and uploads the uniforms, sorting them right away for [DepthSorting::Cpu]
+    ///
+    /// For [DepthSorting::CpuBackground] it requests a sort for this frame and uploads the latest finished one instead.
+    ///
+    /// Follow up with [Renderer::sort] and [Renderer::draw] to render the frame into a pass of your own.
+    pub fn prepare_frame(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        viewport_size: wgpu::Extent3d,
+        camera: &Camera,
+        scenes: &[(&Scene, ModelTransform)],
+    ) -> PreparedFrame {
+        let [view_width, view_height] = camera.view_size;
+        let view_matrix = mat4_orthonormal_inverse(&camera.matrix);
+        let projection_matrix = perspective_projection(view_width, view_height, camera.near, camera.far);
         let view_projection_matrix = mat4_multiplication(&projection_matrix, &view_matrix);
-        let mut splat_count = scene.splat_count;
-        if matches!(self.config.depth_sorting, DepthSorting::Cpu) {
-            let mut entries: Vec<(u32, u32)> = (0..scene.splat_count)
-                .filter_map(|splat_index| {
-                    let world_position = Point::new(
-                        scene.splat_positions[splat_index * 3 + 0],
-                        scene.splat_positions[splat_index * 3 + 1],
-                        scene.splat_positions[splat_index * 3 + 2],
-                        1.0,
-                    );
-                    let homogenous_position = mat4_transform(&view_projection_matrix, &world_position);
-                    let clip_space_position = homogenous_position * (1.0 / homogenous_position[3]);
-                    if clip_space_position[0].abs() < self.config.frustum_culling_tolerance
-                        && clip_space_position[1].abs() < self.config.frustum_culling_tolerance
-                        && (clip_space_position[2] - 0.5).abs() < 0.5
-                    {
-                        Some((unsafe { std::mem::transmute::<f32, u32>(clip_space_position[2]) }, splat_index as u32))
-                    } else {
-                        None
-                    }
-                })
-                .collect();
-            splat_count = entries.len();
-            entries.sort_by(|a, b| a.0.cmp(&b.0));
-            queue.write_buffer(&self.entry_buffer_a, 0, transmute_slice::<_, u8>(&entries));
+        self.gathered_splats
+            .gather(device, queue, &scenes.iter().map(|(scene, _)| *scene).collect::<Vec<_>>());
+        let buffer_offsets = self.gathered_splats.scenes.lock().unwrap();
+        let mut gathered_scenes = Vec::with_capacity(scenes.len());
+        let mut models = Vec::with_capacity(scenes.len());
+        let mut splat_count = 0;
+        for (scene, model_transform) in scenes {
+            let Some(buffer_offset) = GatheredSplats::offset_of(&buffer_offsets, scene) else {
+                continue;
+            };
+            if splat_count + scene.splat_count > self.config.max_splat_count {
+                continue;
+            }
+            if models.len() == MAX_MODEL_COUNT {
+                break;
+            }
+            models.push(Model {
+                transform: model_transform.matrix(),
+                scale: model_transform.scale,
+                splat_offset: splat_count as u32,
+                splat_scale: model_transform.splat_scale,
+                buffer_offset: buffer_offset as u32,
+            });
+            gathered_scenes.push(*scene);
+            splat_count += scene.splat_count;
+        }
+        drop(buffer_offsets);
+        queue.write_buffer(&self.model_buffer, 0, transmute_slice::<_, u8>(&models));
+        let mut instance_count = splat_count;
+        let sort_view = || SortView {
+            model_view_projection_matrices: models
+                .iter()
+                .map(|model| mat4_multiplication(&view_projection_matrix, &model.transform))
+                .collect(),
+            model_splat_offsets: models.iter().map(|model| model.splat_offset as usize).collect(),
+            frustum_culling_tolerance: self.config.frustum_culling_tolerance,
+        };
+        match self.config.depth_sorting {
+            DepthSorting::Cpu => {
+                let sort_view = sort_view();
+                let mut cpu_sorter = self.cpu_sorter.lock().unwrap();
+                cpu_sorter.thread_count = self.config.cpu_sorting_thread_count;
+                let entries = cpu_sorter.sort(splat_count, |splat_index| {
+                    let model_index = sort_view.model_of(splat_index)?;
+                    let scene_splat_index = splat_index - sort_view.model_splat_offsets[model_index];
+                    sort_view.depth_of(
+                        model_index,
+                        &gathered_scenes[model_index].splat_positions[scene_splat_index * 3..scene_splat_index * 3 + 3],
+                    )
+                });
+                instance_count = entries.len();
+                queue.write_buffer(&self.entry_buffer_a, 0, transmute_slice::<_, u8>(entries));
+            }
+            DepthSorting::CpuBackground => {
+                let mut background_sorting = self.background_sorting.lock().unwrap();
+                let background_sorting = background_sorting.get_or_insert_with(|| BackgroundSorting {
+                    sorter: BackgroundSorter::new(),
+                    scene_revisions: Vec::new(),
+                    uploaded_splat_count: 0,
+                    uploaded_instance_count: 0,
+                });
+                let scene_revisions: Vec<u64> = gathered_scenes.iter().map(|scene| scene.revision()).collect();
+                if scene_revisions != background_sorting.scene_revisions {
+                    let positions = gathered_scenes.iter().flat_map(|scene| scene.splat_positions.iter().copied()).collect();
+                    background_sorting.sorter.set_positions(positions);
+                    background_sorting.scene_revisions = scene_revisions;
+                }
+                background_sorting.sorter.request(sort_view(), self.config.cpu_sorting_thread_count);
+                if let Some(sorted) = background_sorting.sorter.latest() {
+                    queue.write_buffer(&self.entry_buffer_a, 0, transmute_slice::<_, u8>(&sorted.entries));
+                    background_sorting.uploaded_splat_count = sorted.splat_count;
+                    background_sorting.uploaded_instance_count = sorted.entries.len();
+                }
+                // Entries sorted for a different number of splats could refer to splats which do not exist anymore
+                instance_count = if background_sorting.uploaded_splat_count == splat_count {
+                    background_sorting.uploaded_instance_count
+                } else {
+                    0
+                };
+            }
+            _ => {}
         }
         let uniform_data = &[Uniforms {
-            camera_matrix,
+            camera_matrix: camera.matrix,
             view_matrix,
             view_projection_matrix,
-            view_size: [view_width, view_height],
+            view_size: camera.view_size,
             image_size: [viewport_size.width, viewport_size.height],
             frustum_culling_tolerance: self.config.frustum_culling_tolerance,
             ellipse_size_bias: 0.2 * view_width / viewport_size.width as f32,
             ellipse_margin: self.config.ellipse_margin,
             splat_scale: self.config.splat_scale,
-            padding: [0.0; 0],
+            splat_count: splat_count as u32,
+            model_count: models.len() as u32,
+            depth_projection: if self.config.reversed_depth {
+                [0.0, camera.near]
+            } else {
+                [
+                    camera.far / (camera.far - camera.near),
+                    -camera.near * camera.far / (camera.far - camera.near),
+                ]
+            },
         }];
         queue.write_buffer(&self.uniform_buffer, 0, transmute_slice::<_, u8>(uniform_data));
-        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        PreparedFrame {
+            splat_count,
+            instance_count,
+            tiled_targets: self
+                .tiled_rasterizer
+                .as_ref()
+                .map(|tiled_rasterizer| tiled_rasterizer.prepare(device, viewport_size)),
+        }
+    }
+
+    /// Records the GPU sorting passes of a frame into `encoder`, does nothing unless [DepthSorting::Gpu], [DepthSorting::GpuIndirectDraw]
+    /// or [DepthSorting::GpuTiled], which rasterizes the splats here as well
+    pub fn sort(&self, encoder: &mut wgpu::CommandEncoder, frame: &PreparedFrame) {
+        let splat_count = frame.splat_count;
+        if let (Some(tiled_rasterizer), Some(tiled_targets)) = (&self.tiled_rasterizer, &frame.tiled_targets) {
+            tiled_rasterizer.rasterize(encoder, &self.compute_bind_groups[0], tiled_targets, splat_count);
+            return;
+        }
         if matches!(self.config.depth_sorting, DepthSorting::Gpu | DepthSorting::GpuIndirectDraw) {
             encoder.clear_buffer(&self.sorting_buffer, 0, None);
             {
                 let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None });
-                compute_pass.set_bind_group(0, &scene.compute_bind_groups[1], &[]);
+                compute_pass.set_bind_group(0, &self.compute_bind_groups[1], &[]);
                 compute_pass.set_pipeline(&self.radix_sort_a_pipeline);
-                compute_pass.dispatch_workgroups(((splat_count + self.workgroup_entries_a - 1) / self.workgroup_entries_a) as u32, 1, 1);
+                compute_pass.dispatch_workgroups(splat_count.div_ceil(self.workgroup_entries_a) as u32, 1, 1);
                 compute_pass.set_pipeline(&self.radix_sort_b_pipeline);
                 compute_pass.dispatch_workgroups(1, self.radix_digit_places as u32, 1);
             }
@@ -214,35 +1062,28 @@ impl Renderer {
                 }
                 let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None });
                 compute_pass.set_pipeline(&self.radix_sort_c_pipeline);
-                compute_pass.set_bind_group(0, &scene.compute_bind_groups[pass_index], &[]);
-                compute_pass.dispatch_workgroups(1, ((splat_count + self.workgroup_entries_c - 1) / self.workgroup_entries_c) as u32, 1);
+                compute_pass.set_bind_group(0, &self.compute_bind_groups[pass_index], &[]);
+                compute_pass.dispatch_workgroups(1, splat_count.div_ceil(self.workgroup_entries_c) as u32, 1);
             }
         }
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: None,
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: frame_view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
-                        store: true,
-                    },
-                })],
-                depth_stencil_attachment: None,
-            });
-            render_pass.set_pipeline(&self.pipeline);
-            if let Some(bind_group) = &scene.render_bind_group {
-                render_pass.set_bind_group(0, bind_group, &[]);
-            }
+    }
+
+    /// Draws the sorted splats of a frame into `render_pass`, which can already contain other geometry
+    ///
+    /// The depth attachment of `render_pass` has to match [Configuration::depth_format].
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, frame: &'a PreparedFrame) {
+        render_pass.set_bind_group(0, &self.render_bind_group, &[]);
+        if let (Some(tiled_rasterizer), Some(tiled_targets)) = (&self.tiled_rasterizer, &frame.tiled_targets) {
+            tiled_rasterizer.draw(render_pass, tiled_targets);
+            return;
+        }
+        for pipeline in std::iter::once(&self.pipeline).chain(self.depth_pipeline.as_ref()) {
+            render_pass.set_pipeline(pipeline);
             if matches!(self.config.depth_sorting, DepthSorting::GpuIndirectDraw) {
                 render_pass.draw_indirect(&self.sorting_buffer, (self.sorting_buffer_size - std::mem::size_of::<u32>() * 5) as u64);
             } else {
-                render_pass.draw(0..4, 0..splat_count as u32);
+                render_pass.draw(0..4, 0..frame.instance_count as u32);
             }
         }
-        queue.submit(Some(encoder.finish()));
     }
 }
-
-
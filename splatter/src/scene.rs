@@ -1,32 +1,134 @@
-use bevy::prelude::*;
-use bevy::render::render_resource::BindGroup;
-use bevy::render::renderer::{RenderDevice, RenderQueue};
-use bevy::render::texture::Image;
+use crate::utils::transmute_slice;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Seek};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use wgpu::util::DeviceExt;
 
-pub struct ScenePlugin;
+#[cfg(feature = "ply")]
+pub mod compressed_ply;
+#[cfg(feature = "ply")]
+pub mod ply;
+#[cfg(feature = "splat-format")]
+pub mod splat_format;
+#[cfg(feature = "compression")]
+pub mod spz;
 
-impl Plugin for ScenePlugin {
-    fn build(&self, app: &mut App) {
-        app.init_resource::<Scene>()
-           .add_systems(Startup, setup_scene);
+#[cfg(feature = "ply")]
+pub use ply::{PlyError, PlyHeader};
+#[cfg(feature = "compression")]
+pub use spz::SpzError;
+
+/// A splat as it is laid out in GPU memory, matches `Splat` in shaders.wgsl
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(C)]
+pub struct GpuSplat {
+    /// Unit quaternion with the real part first
+    pub rotation: [f32; 4],
+    pub center: [f32; 3],
+    pub padding_a: f32,
+    /// Semi axes of the ellipsoid, after the exponential activation
+    pub scale: [f32; 3],
+    /// Opacity, after the sigmoid activation
+    pub alpha: f32,
+    /// Spherical harmonics coefficients, interleaved RGB per coefficient
+    pub color_sh: [f32; 48],
+}
+
+impl Default for GpuSplat {
+    fn default() -> Self {
+        Self {
+            rotation: [1.0, 0.0, 0.0, 0.0],
+            center: [0.0; 3],
+            padding_a: 0.0,
+            scale: [0.0; 3],
+            alpha: 0.0,
+            color_sh: [0.0; 48],
+        }
     }
 }
 
-#[derive(Component)]
-pub struct GaussianBackground;
+/// Coefficient of the first spherical harmonics band, `shc[0]` in shaders.wgsl
+#[cfg(any(feature = "ply", feature = "splat-format"))]
+pub(crate) const SH_C0: f32 = 0.282_094_8;
+
+/// Errors which can occur while loading a scene from a file
+#[derive(Debug)]
+pub enum LoadError {
+    /// The file could not be read or is malformed
+    Io(io::Error),
+    /// The PLY file is malformed or lacks splat properties
+    #[cfg(feature = "ply")]
+    Ply(PlyError),
+    /// The SPZ file is malformed or of an unsupported version
+    #[cfg(feature = "compression")]
+    Spz(SpzError),
+    /// The file extension does not belong to any supported format
+    UnsupportedFileExtension(String),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{}", error),
+            #[cfg(feature = "ply")]
+            Self::Ply(error) => write!(f, "{}", error),
+            #[cfg(feature = "compression")]
+            Self::Spz(error) => write!(f, "{}", error),
+            Self::UnsupportedFileExtension(extension) => write!(f, "unsupported file extension {:?}", extension),
+        }
+    }
+}
 
-#[derive(Component)]
-pub struct Splat {
-    pub splat_file: String,
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            #[cfg(feature = "ply")]
+            Self::Ply(error) => Some(error),
+            #[cfg(feature = "compression")]
+            Self::Spz(error) => Some(error),
+            Self::UnsupportedFileExtension(_) => None,
+        }
+    }
 }
 
-#[derive(Component, Resource)]
+impl From<io::Error> for LoadError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+#[cfg(feature = "ply")]
+impl From<PlyError> for LoadError {
+    fn from(error: PlyError) -> Self {
+        Self::Ply(error)
+    }
+}
+
+#[cfg(feature = "compression")]
+impl From<SpzError> for LoadError {
+    fn from(error: SpzError) -> Self {
+        Self::Spz(error)
+    }
+}
+
+#[cfg(feature = "ply")]
+pub(crate) fn sigmoid(x: f32) -> f32 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+/// Source of [Scene::revision], shared by all scenes so that revisions are never reused
+static NEXT_REVISION: AtomicU64 = AtomicU64::new(0);
+
 pub struct Scene {
     pub splat_count: usize,
-    pub splat_data: Vec<u8>,
+    pub splat_data: Vec<GpuSplat>,
     pub splat_positions: Vec<f32>,
-    pub compute_bind_groups: Vec<BindGroup>,
-    pub render_bind_group: Option<BindGroup>,
+    /// GPU copy of [Scene::splat_data], gathered into `splats` of shaders.wgsl by the renderer
+    pub splat_buffer: Option<wgpu::Buffer>,
+    revision: u64,
 }
 
 impl Scene {
@@ -35,19 +137,166 @@ impl Scene {
             splat_count: 0,
             splat_data: Vec::new(),
             splat_positions: Vec::new(),
-            compute_bind_groups: Vec::new(),
-            render_bind_group: None,
+            splat_buffer: None,
+            revision: NEXT_REVISION.fetch_add(1, Ordering::Relaxed),
         }
     }
 
-    pub fn load_splat_file(&mut self, _path: &str) -> Vec<u8> {
-        // This is a placeholder implementation
-        // In a real implementation, you would read and parse the splat file
-        Vec::new()
+    /// Changes whenever the splats change and is unique across all scenes, so that copies of the splats can tell when they are outdated
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Advances the [Scene::revision], which is necessary after modifying [Scene::splat_positions] or [Scene::splat_buffer] directly
+    pub fn mark_changed(&mut self) {
+        self.revision = NEXT_REVISION.fetch_add(1, Ordering::Relaxed);
     }
 
-    pub fn render(&mut self, render_device: Res<RenderDevice>, render_queue: Res<RenderQueue>, texture: &Image) {
-        // Placeholder for rendering implementation
+    /// Replaces the content of the scene with the splats of the file at `path`, the format is selected by the extension
+    pub fn load_splat_file(&mut self, path: &str) -> Result<(), LoadError> {
+        let extension = Path::new(path)
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        self.load_splat_reader(&mut BufReader::new(File::open(path)?), &extension)
+    }
+
+    /// Lowercase file extensions of the formats whose cargo features are enabled
+    pub const SUPPORTED_EXTENSIONS: &'static [&'static str] = &[
+        #[cfg(feature = "ply")]
+        "ply",
+        #[cfg(feature = "splat-format")]
+        "splat",
+        #[cfg(feature = "compression")]
+        "spz",
+    ];
+
+    /// Replaces the content of the scene with the splats read from `reader`, the format is selected by `extension`
+    ///
+    /// Only the [Scene::SUPPORTED_EXTENSIONS] are supported.
+    #[cfg_attr(not(any(feature = "ply", feature = "splat-format", feature = "compression")), allow(unused_variables))]
+    pub fn load_splat_reader<R: BufRead + Seek>(&mut self, reader: &mut R, extension: &str) -> Result<(), LoadError> {
+        match extension {
+            #[cfg(feature = "ply")]
+            "ply" => Ok(self.load_ply(reader)?),
+            #[cfg(feature = "splat-format")]
+            "splat" => Ok(self.load_splat(reader)?),
+            #[cfg(feature = "compression")]
+            "spz" => Ok(self.load_spz(reader)?),
+            _ => Err(LoadError::UnsupportedFileExtension(extension.to_string())),
+        }
+    }
+
+    /// Replaces the content of the scene with the splats of a `.splat` file
+    #[cfg(feature = "splat-format")]
+    pub fn load_splat<R: io::Read>(&mut self, reader: &mut R) -> io::Result<()> {
+        self.set_splats(splat_format::read_splats(reader)?);
+        Ok(())
+    }
+
+    /// Writes the scene in the `.splat` format, which only keeps the view independent color
+    #[cfg(feature = "splat-format")]
+    pub fn save_splat<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        splat_format::write_splats(writer, &self.splat_data)
+    }
+
+    /// Replaces the content of the scene with the splats of a gzip compressed `.spz` file
+    #[cfg(feature = "compression")]
+    pub fn load_spz<R: io::Read>(&mut self, reader: &mut R) -> Result<(), SpzError> {
+        self.set_splats(spz::read_splats(reader)?);
+        Ok(())
+    }
+
+    /// Writes the scene in the `.spz` format, quantizing all attributes
+    #[cfg(feature = "compression")]
+    pub fn save_spz<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        spz::write_splats(writer, &self.splat_data)
+    }
+
+    /// Replaces the content of the scene with the splats of a PLY file in any of the three encodings, compressed or not
+    #[cfg(feature = "ply")]
+    pub fn load_ply<R: BufRead + Seek>(&mut self, reader: &mut R) -> Result<(), PlyError> {
+        let header = PlyHeader::parse(reader)?;
+        self.set_splats(ply::read_splats(reader, &header, 0..header.splat_count())?);
+        Ok(())
+    }
+
+    /// Writes the scene as a standard binary little endian 3DGS PLY, which [Scene::load_ply] reads back
+    #[cfg(feature = "ply")]
+    pub fn save_ply<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        ply::write_splats(writer, &self.splat_data)
+    }
+
+    /// Parses only the header of a PLY file, returning the header size in bytes and the splat count
+    #[cfg(feature = "ply")]
+    pub fn parse_file_header(mut file: File) -> Result<(u16, usize, File), PlyError> {
+        let mut reader = BufReader::new(&mut file);
+        let header = PlyHeader::parse(&mut reader)?;
+        header.validate()?;
+        let file_header_size = reader.stream_position()?.try_into().map_err(|_| PlyError::HeaderTooLarge)?;
+        Ok((file_header_size, header.splat_count(), file))
+    }
+
+    /// Resizes the scene to `splat_count` invisible splats and allocates the GPU buffer for them
+    ///
+    /// Use [Scene::load_chunk] afterwards to fill it progressively.
+    pub fn allocate(&mut self, device: &wgpu::Device, splat_count: usize) {
+        self.set_splats(vec![GpuSplat::default(); splat_count]);
+        self.create_splat_buffer(device);
+    }
+
+    /// Uploads [Scene::splat_data] into a newly created [Scene::splat_buffer]
+    pub fn create_splat_buffer(&mut self, device: &wgpu::Device) {
+        self.splat_buffer = Some(device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Splat Buffer"),
+            contents: transmute_slice::<_, u8>(&self.splat_data),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+        }));
+    }
+
+    /// Loads the splats in `range` of a PLY file and writes them into [Scene::splat_buffer] if there is one
+    ///
+    /// `file_header_size` is the value returned by [Scene::parse_file_header].
+    #[cfg(feature = "ply")]
+    pub fn load_chunk(&mut self, queue: &wgpu::Queue, file: &mut File, file_header_size: u16, range: std::ops::Range<usize>) -> Result<(), PlyError> {
+        file.seek(io::SeekFrom::Start(0))?;
+        let mut reader = BufReader::new(file);
+        let header = PlyHeader::parse(&mut reader)?;
+        if reader.stream_position()? != file_header_size as u64 {
+            return Err(PlyError::HeaderSizeMismatch);
+        }
+        let splats = ply::read_splats(&mut reader, &header, range.clone())?;
+        let start = range.start.min(self.splat_count.max(header.splat_count()));
+        let offset = (start * std::mem::size_of::<GpuSplat>()) as u64;
+        let data = transmute_slice::<_, u8>(&splats);
+        // Checked before touching anything, so that the splats and their GPU copy stay in sync on errors
+        if let Some(splat_buffer) = &self.splat_buffer {
+            if offset + data.len() as u64 > splat_buffer.size() {
+                return Err(PlyError::ChunkOutOfBounds);
+            }
+        }
+        if self.splat_count < header.splat_count() {
+            self.splat_data.resize(header.splat_count(), GpuSplat::default());
+            self.splat_positions.resize(header.splat_count() * 3, 0.0);
+            self.splat_count = header.splat_count();
+        }
+        for (index, splat) in splats.iter().enumerate() {
+            self.splat_positions[(start + index) * 3..(start + index) * 3 + 3].copy_from_slice(&splat.center);
+        }
+        self.splat_data[start..start + splats.len()].copy_from_slice(&splats);
+        self.mark_changed();
+        if let Some(splat_buffer) = &self.splat_buffer {
+            queue.write_buffer(splat_buffer, offset, data);
+        }
+        Ok(())
+    }
+
+    /// Replaces the content of the scene with `splats`
+    pub fn set_splats(&mut self, splats: Vec<GpuSplat>) {
+        self.splat_count = splats.len();
+        self.splat_positions = splats.iter().flat_map(|splat| splat.center).collect();
+        self.splat_data = splats;
+        self.mark_changed();
     }
 }
 
@@ -56,37 +305,3 @@ impl Default for Scene {
         Self::new()
     }
 }
-
-fn setup_scene(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-) {
-    // Spawn the scene component
-    commands.spawn((
-        Scene::default(),
-        SpatialBundle::default(),
-    ));
-
-    // Create a simple room
-    commands.spawn(PbrBundle {
-        mesh: meshes.add(shape::Box::new(10.0, 5.0, 10.0).into()),
-        material: materials.add(StandardMaterial {
-            base_color: Color::rgb(0.8, 0.8, 0.8),
-            ..default()
-        }),
-        transform: Transform::from_xyz(0.0, 2.5, 0.0),
-        ..default()
-    });
-
-    // Add some props
-    commands.spawn(PbrBundle {
-        mesh: meshes.add(shape::Box::new(1.0, 1.0, 1.0).into()),
-        material: materials.add(StandardMaterial {
-            base_color: Color::rgb(0.4, 0.4, 0.8),
-            ..default()
-        }),
-        transform: Transform::from_xyz(2.0, 0.5, 2.0),
-        ..default()
-    });
-}
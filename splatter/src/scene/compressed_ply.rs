@@ -0,0 +1,302 @@
+//! Decoder for the compressed PLY variant written by PlayCanvas and SuperSplat
+//!
+//! Splats are grouped into chunks of 256, each chunk stores the bounds of the positions, scales and optionally colors.
+//! Every vertex then only stores four packed `uint`s relative to the bounds of its chunk:
+//! 11/10/11 bit position and scale, 2:10:10:10 smallest three rotation and 8/8/8/8 RGBA color.
+//! The optional `sh` element stores the rest coefficients quantized to `uchar`s.
+use super::{
+    ply::{remaining_length, PlyElement, PlyError, PlyFormat, PlyHeader, PlyPropertyType, PlyScalarType},
+    GpuSplat, SH_C0,
+};
+use std::{
+    io::{BufRead, Seek, SeekFrom},
+    ops::Range,
+};
+
+/// Number of splats which share the bounds of one chunk
+pub const CHUNK_SIZE: usize = 256;
+
+const CHUNK_PROPERTIES: [&str; 12] = [
+    "min_x",
+    "min_y",
+    "min_z",
+    "max_x",
+    "max_y",
+    "max_z",
+    "min_scale_x",
+    "min_scale_y",
+    "min_scale_z",
+    "max_scale_x",
+    "max_scale_y",
+    "max_scale_z",
+];
+const CHUNK_COLOR_PROPERTIES: [&str; 6] = ["min_r", "min_g", "min_b", "max_r", "max_g", "max_b"];
+const VERTEX_PROPERTIES: [&str; 4] = ["packed_position", "packed_rotation", "packed_scale", "packed_color"];
+
+/// Bounds shared by the splats of one chunk
+struct Chunk {
+    min_position: [f32; 3],
+    max_position: [f32; 3],
+    min_scale: [f32; 3],
+    max_scale: [f32; 3],
+    min_color: [f32; 3],
+    max_color: [f32; 3],
+}
+
+/// Checks whether the header describes the compressed variant instead of the standard 3DGS layout
+pub(super) fn is_compressed(header: &PlyHeader) -> bool {
+    header.element("chunk").is_some()
+        && header
+            .element("vertex")
+            .is_some_and(|vertex| vertex.property("packed_position").is_some())
+}
+
+fn check_properties<'a>(
+    element: &PlyElement,
+    names: impl Iterator<Item = &'a str>,
+    accepted: &[PlyScalarType],
+    missing: &mut Vec<String>,
+    mistyped: &mut Vec<(String, PlyPropertyType)>,
+) {
+    for name in names {
+        match element.property(name).map(|property| property.property_type) {
+            None => missing.push(name.to_string()),
+            Some(PlyPropertyType::Scalar(scalar_type)) if accepted.contains(&scalar_type) => {}
+            Some(property_type) => mistyped.push((name.to_string(), property_type)),
+        }
+    }
+}
+
+/// Counterpart of [PlyHeader::validate] for the compressed variant
+pub(super) fn validate(header: &PlyHeader) -> Result<(), PlyError> {
+    if header.format == PlyFormat::Ascii {
+        return Err(PlyError::CompressedAscii);
+    }
+    let chunk = header.element("chunk").ok_or(PlyError::MissingVertexElement)?;
+    let vertex = header.element("vertex").ok_or(PlyError::MissingVertexElement)?;
+    let mut missing = Vec::new();
+    let mut mistyped = Vec::new();
+    check_properties(chunk, CHUNK_PROPERTIES.into_iter(), &[PlyScalarType::Float], &mut missing, &mut mistyped);
+    if CHUNK_COLOR_PROPERTIES.iter().any(|name| chunk.property(name).is_some()) {
+        check_properties(
+            chunk,
+            CHUNK_COLOR_PROPERTIES.into_iter(),
+            &[PlyScalarType::Float],
+            &mut missing,
+            &mut mistyped,
+        );
+    }
+    check_properties(
+        vertex,
+        VERTEX_PROPERTIES.into_iter(),
+        &[PlyScalarType::UInt, PlyScalarType::Int],
+        &mut missing,
+        &mut mistyped,
+    );
+    if let Some(sh) = header.element("sh") {
+        let rest_properties = (0..sh.rest_coefficient_count())
+            .map(|index| format!("f_rest_{}", index))
+            .collect::<Vec<_>>();
+        check_properties(
+            sh,
+            rest_properties.iter().map(String::as_str),
+            &[PlyScalarType::UChar],
+            &mut missing,
+            &mut mistyped,
+        );
+    }
+    if !missing.is_empty() || !mistyped.is_empty() {
+        return Err(PlyError::InvalidSchema { missing, mistyped });
+    }
+    if chunk.count.saturating_mul(CHUNK_SIZE) < vertex.count {
+        return Err(PlyError::InsufficientChunks {
+            chunks: chunk.count,
+            splats: vertex.count,
+        });
+    }
+    if let Some(sh) = header.element("sh") {
+        let rest_coefficient_count = sh.rest_coefficient_count();
+        if rest_coefficient_count % 3 != 0 || rest_coefficient_count > 45 || sh.count != vertex.count {
+            return Err(PlyError::UnsupportedShCoefficientCount(rest_coefficient_count));
+        }
+    }
+    for element in &header.elements {
+        element.checked_record_size()?;
+    }
+    Ok(())
+}
+
+fn read_u32(bytes: &[u8], format: PlyFormat) -> u32 {
+    let bytes = bytes[0..4].try_into().unwrap();
+    if format == PlyFormat::BinaryBigEndian {
+        u32::from_be_bytes(bytes)
+    } else {
+        u32::from_le_bytes(bytes)
+    }
+}
+
+fn unorm(value: u32, bits: u32) -> f32 {
+    let max = (1 << bits) - 1;
+    (value & max) as f32 / max as f32
+}
+
+fn lerp(min: [f32; 3], max: [f32; 3], t: [f32; 3]) -> [f32; 3] {
+    [0, 1, 2].map(|axis| min[axis] + (max[axis] - min[axis]) * t[axis])
+}
+
+fn unpack_111011(value: u32) -> [f32; 3] {
+    [unorm(value >> 21, 11), unorm(value >> 11, 10), unorm(value, 11)]
+}
+
+/// Restores the largest component of the quaternion, whose index is stored in the upper two bits
+fn unpack_rotation(value: u32) -> [f32; 4] {
+    let [a, b, c] = [20, 10, 0].map(|shift| (unorm(value >> shift, 10) - 0.5) * std::f32::consts::SQRT_2);
+    let m = (1.0 - (a * a + b * b + c * c)).max(0.0).sqrt();
+    match value >> 30 {
+        0 => [m, a, b, c],
+        1 => [a, m, b, c],
+        2 => [a, b, m, c],
+        _ => [a, b, c, m],
+    }
+}
+
+fn unpack_sh(value: u8) -> f32 {
+    let normalized = match value {
+        0 => 0.0,
+        255 => 1.0,
+        _ => (value as f32 + 0.5) / 256.0,
+    };
+    (normalized - 0.5) * 8.0
+}
+
+/// Reads `count` records of `element` starting at record `first`, `element_start` is the byte offset of the element
+fn read_records<R: BufRead + Seek>(
+    reader: &mut R,
+    element_start: u64,
+    element: &PlyElement,
+    first: usize,
+    count: usize,
+) -> Result<Vec<u8>, PlyError> {
+    let record_size = element.checked_record_size()? as u64;
+    reader.seek(SeekFrom::Start(element_start.saturating_add((first as u64).saturating_mul(record_size))))?;
+    // The counts of the header are untrusted, so check that the file actually holds the records before allocating them
+    let length = (count as u64).saturating_mul(record_size);
+    if length > remaining_length(reader)? {
+        return Err(PlyError::UnexpectedEndOfBody);
+    }
+    let mut data = vec![0; length as usize];
+    reader.read_exact(&mut data)?;
+    Ok(data)
+}
+
+/// Reads and decodes the splats in `range`, the reader must be positioned right after the header
+pub(super) fn read_splats<R: BufRead + Seek>(reader: &mut R, header: &PlyHeader, range: Range<usize>) -> Result<Vec<GpuSplat>, PlyError> {
+    validate(header)?;
+    let vertex = header.element("vertex").unwrap();
+    let range = range.start.min(vertex.count)..range.end.min(vertex.count);
+    if range.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // All elements have a fixed record size, so their byte offsets are known upfront
+    let mut element_start = reader.stream_position()?;
+    let mut element_starts = Vec::with_capacity(header.elements.len());
+    for element in &header.elements {
+        element_starts.push(element_start);
+        element_start = element_start.saturating_add((element.checked_record_size()? as u64).saturating_mul(element.count as u64));
+    }
+    let element_start = |name: &str| element_starts[header.elements.iter().position(|element| element.name == name).unwrap()];
+
+    let chunk_element = header.element("chunk").unwrap();
+    let chunk_range = range.start / CHUNK_SIZE..range.end.div_ceil(CHUNK_SIZE);
+    let chunk_data = read_records(reader, element_start("chunk"), chunk_element, chunk_range.start, chunk_range.len())?;
+    let chunk_record_size = chunk_element.checked_record_size()?;
+    let has_color_bounds = chunk_element.property("min_r").is_some();
+    let chunks = chunk_data
+        .chunks_exact(chunk_record_size)
+        .map(|record| {
+            let float = |name: &str| match chunk_element.property(name) {
+                Some(property) => PlyScalarType::Float.decode(&record[property.offset.unwrap()..], header.format),
+                None => 0.0,
+            };
+            Chunk {
+                min_position: [float("min_x"), float("min_y"), float("min_z")],
+                max_position: [float("max_x"), float("max_y"), float("max_z")],
+                min_scale: [float("min_scale_x"), float("min_scale_y"), float("min_scale_z")],
+                max_scale: [float("max_scale_x"), float("max_scale_y"), float("max_scale_z")],
+                min_color: if has_color_bounds {
+                    [float("min_r"), float("min_g"), float("min_b")]
+                } else {
+                    [0.0; 3]
+                },
+                max_color: if has_color_bounds {
+                    [float("max_r"), float("max_g"), float("max_b")]
+                } else {
+                    [1.0; 3]
+                },
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let vertex_data = read_records(reader, element_start("vertex"), vertex, range.start, range.len())?;
+    let vertex_record_size = vertex.checked_record_size()?;
+    let [packed_position, packed_rotation, packed_scale, packed_color] = VERTEX_PROPERTIES.map(|name| vertex.property(name).unwrap().offset.unwrap());
+
+    let sh = header.element("sh");
+    let sh_data = match sh {
+        Some(sh) => read_records(reader, element_start("sh"), sh, range.start, range.len())?,
+        None => Vec::new(),
+    };
+    let rest_offsets = sh.map_or(Vec::new(), |sh| {
+        (0..sh.rest_coefficient_count())
+            .map(|index| sh.property(&format!("f_rest_{}", index)).unwrap().offset.unwrap())
+            .collect()
+    });
+    let sh_record_size = sh.map_or(0, |sh| sh.checked_record_size().unwrap_or(0));
+    let coefficients_per_channel = rest_offsets.len() / 3;
+
+    let splats = range
+        .clone()
+        .enumerate()
+        .map(|(index, splat_index)| {
+            let chunk = &chunks[splat_index / CHUNK_SIZE - chunk_range.start];
+            let record = &vertex_data[index * vertex_record_size..(index + 1) * vertex_record_size];
+            let color = read_u32(&record[packed_color..], header.format);
+            let color_t = [unorm(color >> 24, 8), unorm(color >> 16, 8), unorm(color >> 8, 8)];
+            let color = lerp(chunk.min_color, chunk.max_color, color_t);
+            let mut color_sh = [0.0; 48];
+            for channel in 0..3 {
+                color_sh[channel] = (color[channel] - 0.5) / SH_C0;
+            }
+            if coefficients_per_channel > 0 {
+                let sh_record = &sh_data[index * sh_record_size..(index + 1) * sh_record_size];
+                for channel in 0..3 {
+                    for coefficient in 0..coefficients_per_channel {
+                        let offset = rest_offsets[channel * coefficients_per_channel + coefficient];
+                        color_sh[(coefficient + 1) * 3 + channel] = unpack_sh(sh_record[offset]);
+                    }
+                }
+            }
+            let rotation = unpack_rotation(read_u32(&record[packed_rotation..], header.format));
+            let norm = rotation.iter().map(|value| value * value).sum::<f32>().sqrt();
+            let log_scale = lerp(
+                chunk.min_scale,
+                chunk.max_scale,
+                unpack_111011(read_u32(&record[packed_scale..], header.format)),
+            );
+            GpuSplat {
+                rotation: rotation.map(|value| value / norm),
+                center: lerp(
+                    chunk.min_position,
+                    chunk.max_position,
+                    unpack_111011(read_u32(&record[packed_position..], header.format)),
+                ),
+                padding_a: 0.0,
+                scale: log_scale.map(f32::exp),
+                alpha: unorm(read_u32(&record[packed_color..], header.format), 8),
+                color_sh,
+            }
+        })
+        .collect();
+    Ok(splats)
+}
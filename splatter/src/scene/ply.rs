@@ -0,0 +1,587 @@
+//! Parser for the PLY files written by 3D gaussian splatting trainers
+use super::{compressed_ply, sigmoid, GpuSplat};
+use std::{
+    fmt,
+    io::{self, BufRead, Seek, SeekFrom, Write},
+    ops::Range,
+};
+
+/// Properties every splat vertex must have, the `f_rest_*` coefficients are optional
+const REQUIRED_PROPERTIES: [&str; 14] = [
+    "x", "y", "z", "f_dc_0", "f_dc_1", "f_dc_2", "opacity", "scale_0", "scale_1", "scale_2", "rot_0", "rot_1", "rot_2", "rot_3",
+];
+
+/// Errors which can occur while reading a PLY file
+#[derive(Debug)]
+pub enum PlyError {
+    /// The underlying reader failed
+    Io(io::Error),
+    /// The file does not start with the `ply` magic number
+    MissingMagicNumber,
+    /// The file ended before `end_header`
+    UnexpectedEndOfHeader,
+    /// The file ended before all records the header declares
+    UnexpectedEndOfBody,
+    /// The header does not declare a format
+    MissingFormat,
+    /// The header declares a format other than ascii, binary_little_endian or binary_big_endian
+    UnknownFormat(String),
+    /// A line of the header could not be parsed
+    MalformedHeaderLine(String),
+    /// A property has a type which is not part of the PLY specification
+    UnknownPropertyType(String),
+    /// The header does not declare a `vertex` element
+    MissingVertexElement,
+    /// Required splat properties of the `vertex` element are absent or not floating point
+    InvalidSchema {
+        /// Names of the required properties which are absent
+        missing: Vec<String>,
+        /// Names and types of the properties which are present but have an unsupported type
+        mistyped: Vec<(String, PlyPropertyType)>,
+    },
+    /// The number of `f_rest_*` properties does not correspond to a spherical harmonics order of at most 3
+    UnsupportedShCoefficientCount(usize),
+    /// A binary element contains a list property, so its records do not have a fixed size
+    UnsupportedListProperty { element: String, property: String },
+    /// A line of an ascii body could not be parsed
+    MalformedVertex(String),
+    /// The compressed variant is only defined for binary encodings
+    CompressedAscii,
+    /// A compressed PLY file has fewer chunks than its splats need
+    InsufficientChunks { chunks: usize, splats: usize },
+    /// The header exceeds the size which can be passed to [super::Scene::load_chunk]
+    HeaderTooLarge,
+    /// The header size passed to [super::Scene::load_chunk] does not match the file
+    HeaderSizeMismatch,
+    /// The requested range does not fit into the allocated [super::Scene::splat_buffer]
+    ChunkOutOfBounds,
+}
+
+impl fmt::Display for PlyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{}", error),
+            Self::MissingMagicNumber => write!(f, "missing ply magic number"),
+            Self::UnexpectedEndOfHeader => write!(f, "unexpected end of file in header"),
+            Self::UnexpectedEndOfBody => write!(f, "unexpected end of file in body"),
+            Self::MissingFormat => write!(f, "missing format line"),
+            Self::UnknownFormat(format) => write!(f, "unknown format {}", format),
+            Self::MalformedHeaderLine(line) => write!(f, "malformed header line: {}", line),
+            Self::UnknownPropertyType(name) => write!(f, "unknown property type {}", name),
+            Self::MissingVertexElement => write!(f, "missing vertex element"),
+            Self::InvalidSchema { missing, mistyped } => {
+                write!(f, "invalid splat properties")?;
+                if !missing.is_empty() {
+                    write!(f, ", missing: {}", missing.join(", "))?;
+                }
+                for (name, property_type) in mistyped {
+                    write!(f, ", {} has type {} instead of float or double", name, property_type)?;
+                }
+                Ok(())
+            }
+            Self::UnsupportedShCoefficientCount(count) => write!(f, "unsupported number of f_rest properties: {}", count),
+            Self::UnsupportedListProperty { element, property } => {
+                write!(f, "list property {} of element {} is not supported", property, element)
+            }
+            Self::MalformedVertex(line) => write!(f, "malformed vertex: {}", line),
+            Self::CompressedAscii => write!(f, "compressed PLY files must be binary encoded"),
+            Self::InsufficientChunks { chunks, splats } => write!(f, "{} chunks are not enough for {} splats", chunks, splats),
+            Self::HeaderTooLarge => write!(f, "header is too large"),
+            Self::HeaderSizeMismatch => write!(f, "file header size does not match the file"),
+            Self::ChunkOutOfBounds => write!(f, "chunk exceeds the allocated splat buffer"),
+        }
+    }
+}
+
+impl std::error::Error for PlyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for PlyError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// Encoding of the body of a PLY file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlyFormat {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+/// Scalar types a PLY property can have
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlyScalarType {
+    Char,
+    UChar,
+    Short,
+    UShort,
+    Int,
+    UInt,
+    Float,
+    Double,
+}
+
+impl PlyScalarType {
+    fn parse(name: &str) -> Result<Self, PlyError> {
+        Ok(match name {
+            "char" | "int8" => Self::Char,
+            "uchar" | "uint8" => Self::UChar,
+            "short" | "int16" => Self::Short,
+            "ushort" | "uint16" => Self::UShort,
+            "int" | "int32" => Self::Int,
+            "uint" | "uint32" => Self::UInt,
+            "float" | "float32" => Self::Float,
+            "double" | "float64" => Self::Double,
+            _ => return Err(PlyError::UnknownPropertyType(name.to_string())),
+        })
+    }
+
+    /// Size of a binary encoded value in bytes
+    pub fn size(self) -> usize {
+        match self {
+            Self::Char | Self::UChar => 1,
+            Self::Short | Self::UShort => 2,
+            Self::Int | Self::UInt | Self::Float => 4,
+            Self::Double => 8,
+        }
+    }
+
+    pub(super) fn decode(self, bytes: &[u8], format: PlyFormat) -> f32 {
+        macro_rules! decode {
+            ($type:ty) => {{
+                let bytes = bytes[0..std::mem::size_of::<$type>()].try_into().unwrap();
+                (if format == PlyFormat::BinaryBigEndian {
+                    <$type>::from_be_bytes(bytes)
+                } else {
+                    <$type>::from_le_bytes(bytes)
+                }) as f32
+            }};
+        }
+        match self {
+            Self::Char => decode!(i8),
+            Self::UChar => decode!(u8),
+            Self::Short => decode!(i16),
+            Self::UShort => decode!(u16),
+            Self::Int => decode!(i32),
+            Self::UInt => decode!(u32),
+            Self::Float => decode!(f32),
+            Self::Double => decode!(f64),
+        }
+    }
+}
+
+impl fmt::Display for PlyScalarType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Char => "char",
+            Self::UChar => "uchar",
+            Self::Short => "short",
+            Self::UShort => "ushort",
+            Self::Int => "int",
+            Self::UInt => "uint",
+            Self::Float => "float",
+            Self::Double => "double",
+        })
+    }
+}
+
+/// Type of a PLY property
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlyPropertyType {
+    Scalar(PlyScalarType),
+    /// Variable length list, prefixed by its length
+    List {
+        length: PlyScalarType,
+        item: PlyScalarType,
+    },
+}
+
+impl fmt::Display for PlyPropertyType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Scalar(scalar_type) => write!(f, "{}", scalar_type),
+            Self::List { length, item } => write!(f, "list {} {}", length, item),
+        }
+    }
+}
+
+/// A property declared in the header
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlyProperty {
+    pub name: String,
+    pub property_type: PlyPropertyType,
+    /// Byte offset inside a binary record, [None] if it follows a list property
+    pub offset: Option<usize>,
+}
+
+/// An element declared in the header
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlyElement {
+    pub name: String,
+    pub count: usize,
+    pub properties: Vec<PlyProperty>,
+}
+
+impl PlyElement {
+    /// Looks up a property by name
+    pub fn property(&self, name: &str) -> Option<&PlyProperty> {
+        self.properties.iter().find(|property| property.name == name)
+    }
+
+    /// Size of one binary record, [None] if the element contains list properties
+    pub fn record_size(&self) -> Option<usize> {
+        self.properties.iter().try_fold(0, |size, property| match property.property_type {
+            PlyPropertyType::Scalar(scalar_type) => Some(size + scalar_type.size()),
+            PlyPropertyType::List { .. } => None,
+        })
+    }
+
+    pub(super) fn checked_record_size(&self) -> Result<usize, PlyError> {
+        self.record_size().ok_or_else(|| PlyError::UnsupportedListProperty {
+            element: self.name.clone(),
+            property: self
+                .properties
+                .iter()
+                .find(|property| matches!(property.property_type, PlyPropertyType::List { .. }))
+                .map(|property| property.name.clone())
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Number of `f_rest_0`, `f_rest_1`, ... properties which are declared without gaps
+    pub fn rest_coefficient_count(&self) -> usize {
+        (0..).take_while(|index| self.property(&format!("f_rest_{}", index)).is_some()).count()
+    }
+}
+
+/// The typed header of a PLY file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlyHeader {
+    pub format: PlyFormat,
+    pub elements: Vec<PlyElement>,
+}
+
+impl PlyHeader {
+    /// Parses everything up to and including the `end_header` line
+    pub fn parse<R: BufRead>(reader: &mut R) -> Result<Self, PlyError> {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        if line.trim_end() != "ply" {
+            return Err(PlyError::MissingMagicNumber);
+        }
+        let mut format = None;
+        let mut elements: Vec<PlyElement> = Vec::new();
+        // Running byte offset of the next property, [None] once a list property was encountered
+        let mut offset = Some(0);
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                return Err(PlyError::UnexpectedEndOfHeader);
+            }
+            let malformed = || PlyError::MalformedHeaderLine(line.trim_end().to_string());
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("format") => {
+                    format = Some(match words.next() {
+                        Some("ascii") => PlyFormat::Ascii,
+                        Some("binary_little_endian") => PlyFormat::BinaryLittleEndian,
+                        Some("binary_big_endian") => PlyFormat::BinaryBigEndian,
+                        Some(other) => return Err(PlyError::UnknownFormat(other.to_string())),
+                        None => return Err(malformed()),
+                    });
+                }
+                Some("element") => {
+                    let (Some(name), Some(count)) = (words.next(), words.next().and_then(|count| count.parse().ok())) else {
+                        return Err(malformed());
+                    };
+                    elements.push(PlyElement {
+                        name: name.to_string(),
+                        count,
+                        properties: Vec::new(),
+                    });
+                    offset = Some(0);
+                }
+                Some("property") => {
+                    let Some(element) = elements.last_mut() else {
+                        return Err(malformed());
+                    };
+                    let property_type = match words.next() {
+                        Some("list") => {
+                            let (Some(length), Some(item)) = (words.next(), words.next()) else {
+                                return Err(malformed());
+                            };
+                            PlyPropertyType::List {
+                                length: PlyScalarType::parse(length)?,
+                                item: PlyScalarType::parse(item)?,
+                            }
+                        }
+                        Some(type_name) => PlyPropertyType::Scalar(PlyScalarType::parse(type_name)?),
+                        None => return Err(malformed()),
+                    };
+                    let Some(name) = words.next() else {
+                        return Err(malformed());
+                    };
+                    element.properties.push(PlyProperty {
+                        name: name.to_string(),
+                        property_type,
+                        offset,
+                    });
+                    offset = match property_type {
+                        PlyPropertyType::Scalar(scalar_type) => offset.map(|offset| offset + scalar_type.size()),
+                        PlyPropertyType::List { .. } => None,
+                    };
+                }
+                Some("end_header") => break,
+                _ => {}
+            }
+        }
+        Ok(Self {
+            format: format.ok_or(PlyError::MissingFormat)?,
+            elements,
+        })
+    }
+
+    /// Looks up an element by name
+    pub fn element(&self, name: &str) -> Option<&PlyElement> {
+        self.elements.iter().find(|element| element.name == name)
+    }
+
+    /// Number of splats stored in the `vertex` element
+    pub fn splat_count(&self) -> usize {
+        self.element("vertex").map_or(0, |element| element.count)
+    }
+
+    /// Whether this is the chunked and quantized variant written by PlayCanvas and SuperSplat
+    pub fn is_compressed(&self) -> bool {
+        compressed_ply::is_compressed(self)
+    }
+
+    /// Checks that the `vertex` element has all properties required to decode splats
+    ///
+    /// Reports all missing and mistyped properties at once. Additional properties such as
+    /// normals or `red` / `green` / `blue` are allowed and ignored.
+    pub fn validate(&self) -> Result<(), PlyError> {
+        if self.is_compressed() {
+            return compressed_ply::validate(self);
+        }
+        let vertex = self.element("vertex").ok_or(PlyError::MissingVertexElement)?;
+        let rest_coefficient_count = vertex.rest_coefficient_count();
+        let mut missing = Vec::new();
+        let mut mistyped = Vec::new();
+        let rest_properties = (0..rest_coefficient_count).map(|index| format!("f_rest_{}", index));
+        for name in REQUIRED_PROPERTIES.iter().map(|name| name.to_string()).chain(rest_properties) {
+            match vertex.property(&name).map(|property| property.property_type) {
+                None => missing.push(name),
+                Some(PlyPropertyType::Scalar(PlyScalarType::Float | PlyScalarType::Double)) => {}
+                Some(property_type) => mistyped.push((name, property_type)),
+            }
+        }
+        if !missing.is_empty() || !mistyped.is_empty() {
+            return Err(PlyError::InvalidSchema { missing, mistyped });
+        }
+        if rest_coefficient_count % 3 != 0 || rest_coefficient_count > 45 {
+            return Err(PlyError::UnsupportedShCoefficientCount(rest_coefficient_count));
+        }
+        if self.format != PlyFormat::Ascii {
+            for element in &self.elements {
+                element.checked_record_size()?;
+                if element.name == "vertex" {
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Where each component of a splat is found among the properties of a vertex
+struct Layout {
+    center: [usize; 3],
+    color_dc: [usize; 3],
+    color_rest: Vec<usize>,
+    opacity: usize,
+    scale: [usize; 3],
+    rotation: [usize; 4],
+}
+
+impl Layout {
+    /// Expects the header to have passed [PlyHeader::validate]
+    fn new(vertex: &PlyElement) -> Self {
+        let find = |name: &str| vertex.properties.iter().position(|property| property.name == name).unwrap();
+        Self {
+            center: ["x", "y", "z"].map(find),
+            color_dc: ["f_dc_0", "f_dc_1", "f_dc_2"].map(find),
+            color_rest: (0..vertex.rest_coefficient_count())
+                .map(|index| find(&format!("f_rest_{}", index)))
+                .collect(),
+            opacity: find("opacity"),
+            scale: ["scale_0", "scale_1", "scale_2"].map(find),
+            rotation: ["rot_0", "rot_1", "rot_2", "rot_3"].map(find),
+        }
+    }
+
+    /// Applies the activation functions and reorders the coefficients into the layout of [GpuSplat]
+    fn decode(&self, values: &[f32]) -> GpuSplat {
+        let mut rotation = self.rotation.map(|index| values[index]);
+        let norm = rotation.iter().map(|value| value * value).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            rotation = rotation.map(|value| value / norm);
+        }
+        let mut color_sh = [0.0; 48];
+        for channel in 0..3 {
+            color_sh[channel] = values[self.color_dc[channel]];
+        }
+        // The rest coefficients are stored channel by channel, the GPU expects them interleaved
+        let coefficients_per_channel = self.color_rest.len() / 3;
+        for channel in 0..3 {
+            for coefficient in 0..coefficients_per_channel {
+                color_sh[(coefficient + 1) * 3 + channel] = values[self.color_rest[channel * coefficients_per_channel + coefficient]];
+            }
+        }
+        GpuSplat {
+            rotation,
+            center: self.center.map(|index| values[index]),
+            padding_a: 0.0,
+            scale: self.scale.map(|index| values[index].exp()),
+            alpha: sigmoid(values[self.opacity]),
+            color_sh,
+        }
+    }
+}
+
+/// Reads the splats in `range` of the `vertex` element, the reader must be positioned right after the header
+pub(crate) fn read_splats<R: BufRead + Seek>(reader: &mut R, header: &PlyHeader, range: Range<usize>) -> Result<Vec<GpuSplat>, PlyError> {
+    if header.is_compressed() {
+        return compressed_ply::read_splats(reader, header, range);
+    }
+    header.validate()?;
+    for element in &header.elements {
+        if element.name != "vertex" {
+            // Skip elements which precede the vertices
+            skip_records(reader, header.format, element, element.count)?;
+            continue;
+        }
+        let range = range.start.min(element.count)..range.end.min(element.count);
+        let layout = Layout::new(element);
+        let mut values = vec![0.0; element.properties.len()];
+        skip_records(reader, header.format, element, range.start)?;
+        // The counts of the header are untrusted, so never reserve more than the rest of the file could hold
+        let remaining_length = remaining_length(reader)?;
+        let mut splats = Vec::new();
+        if header.format == PlyFormat::Ascii {
+            // Every value takes at least one digit and one separator
+            splats.reserve(range.len().min((remaining_length / (2 * element.properties.len()) as u64) as usize));
+            let mut line = String::new();
+            for _ in range {
+                line.clear();
+                if reader.read_line(&mut line)? == 0 {
+                    return Err(PlyError::UnexpectedEndOfBody);
+                }
+                let mut words = line.split_whitespace();
+                for value in values.iter_mut() {
+                    *value = words
+                        .next()
+                        .and_then(|word| word.parse().ok())
+                        .ok_or_else(|| PlyError::MalformedVertex(line.trim_end().to_string()))?;
+                }
+                splats.push(layout.decode(&values));
+            }
+        } else {
+            let record_size = element.checked_record_size()?;
+            if (range.len() as u64).saturating_mul(record_size as u64) > remaining_length {
+                return Err(PlyError::UnexpectedEndOfBody);
+            }
+            splats.reserve(range.len());
+            let mut record = vec![0; record_size];
+            for _ in range {
+                reader.read_exact(&mut record)?;
+                for (value, property) in values.iter_mut().zip(element.properties.iter()) {
+                    if let (PlyPropertyType::Scalar(scalar_type), Some(offset)) = (property.property_type, property.offset) {
+                        *value = scalar_type.decode(&record[offset..], header.format);
+                    }
+                }
+                splats.push(layout.decode(&values));
+            }
+        }
+        return Ok(splats);
+    }
+    Err(PlyError::MissingVertexElement)
+}
+
+/// Number of bytes between the current position of `reader` and the end of the file
+pub(super) fn remaining_length<R: Seek>(reader: &mut R) -> io::Result<u64> {
+    let position = reader.stream_position()?;
+    let end = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(position))?;
+    Ok(end.saturating_sub(position))
+}
+
+fn skip_records<R: BufRead + Seek>(reader: &mut R, format: PlyFormat, element: &PlyElement, count: usize) -> Result<(), PlyError> {
+    if format == PlyFormat::Ascii {
+        let mut line = String::new();
+        for _ in 0..count {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                return Err(PlyError::UnexpectedEndOfBody);
+            }
+        }
+    } else {
+        let length = element
+            .checked_record_size()?
+            .checked_mul(count)
+            .and_then(|length| i64::try_from(length).ok());
+        reader.seek(SeekFrom::Current(length.ok_or(PlyError::UnexpectedEndOfBody)?))?;
+    }
+    Ok(())
+}
+
+/// Writes `splats` as a binary little endian PLY in the layout of the reference 3DGS implementation
+///
+/// All 45 rest coefficients are written, so that trainers configured for degree 3 accept the file.
+/// Keeps the inverse of the sigmoid finite for fully transparent and fully opaque splats
+const ALPHA_EPSILON: f32 = 1.0e-6;
+
+pub(crate) fn write_splats<W: Write>(writer: &mut W, splats: &[GpuSplat]) -> io::Result<()> {
+    let mut header = format!("ply\nformat binary_little_endian 1.0\nelement vertex {}\n", splats.len());
+    for name in ["x", "y", "z", "nx", "ny", "nz", "f_dc_0", "f_dc_1", "f_dc_2"] {
+        header += &format!("property float {}\n", name);
+    }
+    for index in 0..45 {
+        header += &format!("property float f_rest_{}\n", index);
+    }
+    for name in ["opacity", "scale_0", "scale_1", "scale_2", "rot_0", "rot_1", "rot_2", "rot_3"] {
+        header += &format!("property float {}\n", name);
+    }
+    header += "end_header\n";
+    writer.write_all(header.as_bytes())?;
+    let mut values = Vec::with_capacity(62);
+    for splat in splats {
+        values.clear();
+        values.extend_from_slice(&splat.center);
+        values.extend_from_slice(&[0.0; 3]);
+        values.extend_from_slice(&splat.color_sh[0..3]);
+        // Undo the interleaving of [Layout::decode]
+        for channel in 0..3 {
+            for coefficient in 0..15 {
+                values.push(splat.color_sh[(coefficient + 1) * 3 + channel]);
+            }
+        }
+        // Inverse of the sigmoid and exp activations, clamped so that they stay finite
+        let alpha = splat.alpha.clamp(ALPHA_EPSILON, 1.0 - ALPHA_EPSILON);
+        values.push((alpha / (1.0 - alpha)).ln());
+        values.extend(splat.scale.iter().map(|scale| scale.max(f32::MIN_POSITIVE).ln()));
+        values.extend_from_slice(&splat.rotation);
+        for value in &values {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,74 @@
+//! Reader and writer for the compact `.splat` format popularized by antimatter15/splat
+//!
+//! Every splat occupies 32 bytes: float3 position, float3 scale, uchar4 RGBA and uchar4 rotation quaternion.
+//! Scale and opacity are stored after activation, the color is the view independent part only.
+use super::{GpuSplat, SH_C0};
+use std::io::{self, Read, Write};
+
+/// Size of one splat record in bytes
+pub const RECORD_SIZE: usize = 32;
+
+/// Reads splats until the end of the reader is reached
+pub fn read_splats<R: Read>(reader: &mut R) -> io::Result<Vec<GpuSplat>> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    if data.len() % RECORD_SIZE != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("file size {} is not a multiple of {}", data.len(), RECORD_SIZE),
+        ));
+    }
+    Ok(data.chunks_exact(RECORD_SIZE).map(decode).collect())
+}
+
+/// Writes `splats`, dropping all spherical harmonics coefficients beyond the first
+pub fn write_splats<W: Write>(writer: &mut W, splats: &[GpuSplat]) -> io::Result<()> {
+    for splat in splats {
+        writer.write_all(&encode(splat))?;
+    }
+    Ok(())
+}
+
+fn decode(record: &[u8]) -> GpuSplat {
+    let float = |index: usize| f32::from_le_bytes(record[index * 4..index * 4 + 4].try_into().unwrap());
+    let mut rotation = [0.0; 4];
+    for (component, byte) in rotation.iter_mut().zip(record[28..32].iter()) {
+        *component = (*byte as f32 - 128.0) / 128.0;
+    }
+    let norm = rotation.iter().map(|value| value * value).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        rotation = rotation.map(|value| value / norm);
+    } else {
+        rotation = [1.0, 0.0, 0.0, 0.0];
+    }
+    let mut color_sh = [0.0; 48];
+    // Invert the evaluation of the first spherical harmonics band in sphericalHarmonicsLookup
+    for channel in 0..3 {
+        color_sh[channel] = (record[24 + channel] as f32 / 255.0 - 0.5) / SH_C0;
+    }
+    GpuSplat {
+        rotation,
+        center: [float(0), float(1), float(2)],
+        padding_a: 0.0,
+        scale: [float(3), float(4), float(5)],
+        alpha: record[27] as f32 / 255.0,
+        color_sh,
+    }
+}
+
+fn encode(splat: &GpuSplat) -> [u8; RECORD_SIZE] {
+    let quantize = |value: f32| (value * 255.0).round().clamp(0.0, 255.0) as u8;
+    let mut record = [0; RECORD_SIZE];
+    for (index, value) in splat.center.iter().chain(splat.scale.iter()).enumerate() {
+        record[index * 4..index * 4 + 4].copy_from_slice(&value.to_le_bytes());
+    }
+    for channel in 0..3 {
+        record[24 + channel] = quantize(0.5 + SH_C0 * splat.color_sh[channel]);
+    }
+    record[27] = quantize(splat.alpha);
+    let norm = splat.rotation.iter().map(|value| value * value).sum::<f32>().sqrt().max(f32::EPSILON);
+    for (byte, component) in record[28..32].iter_mut().zip(splat.rotation.iter()) {
+        *byte = (component / norm * 128.0 + 128.0).round().clamp(0.0, 255.0) as u8;
+    }
+    record
+}
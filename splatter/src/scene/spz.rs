@@ -0,0 +1,259 @@
+//! Reader and writer for the gzip framed SPZ format by Niantic
+//!
+//! After a 16 byte header all splats are stored attribute by attribute: 24 bit fixed point positions, alpha bytes,
+//! color bytes, log scale bytes, quaternions and quantized spherical harmonics. SPZ stores everything in a
+//! right-up-back coordinate system, while [super::Scene] uses the right-down-front convention of the PLY trainers,
+//! so the Y and Z axes are flipped while reading and writing.
+use super::GpuSplat;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use std::{
+    fmt,
+    io::{self, Read, Write},
+};
+
+/// "NGSP" in little endian
+const MAGIC: u32 = 0x5053474e;
+/// Version written by [write_splats], which uses the smallest three quaternion encoding
+const VERSION: u32 = 3;
+/// Fractional bits of the fixed point positions written by [write_splats]
+const FRACTIONAL_BITS: u8 = 12;
+/// Scale applied to the first spherical harmonics band before quantizing it
+const COLOR_SCALE: f32 = 0.15;
+/// Sign changes of the rest coefficients when flipping the Y and Z axes
+const SH_FLIP: [f32; 15] = [-1.0, -1.0, 1.0, -1.0, 1.0, 1.0, -1.0, 1.0, -1.0, 1.0, -1.0, -1.0, 1.0, -1.0, 1.0];
+
+/// Errors which can occur while reading an SPZ file
+#[derive(Debug)]
+pub enum SpzError {
+    /// The underlying reader failed, also covers corrupted gzip streams and truncated files
+    Io(io::Error),
+    /// The decompressed data does not start with the `NGSP` magic number
+    InvalidMagic(u32),
+    /// Only versions 2 and 3 are supported
+    UnsupportedVersion(u32),
+    /// The spherical harmonics degree exceeds 3
+    UnsupportedShDegree(u8),
+    /// The fixed point positions have more than 23 fractional bits
+    UnsupportedFractionalBits(u8),
+}
+
+impl fmt::Display for SpzError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{}", error),
+            Self::InvalidMagic(magic) => write!(f, "invalid magic number {:#010x}", magic),
+            Self::UnsupportedVersion(version) => write!(f, "unsupported version {}", version),
+            Self::UnsupportedShDegree(degree) => write!(f, "unsupported spherical harmonics degree {}", degree),
+            Self::UnsupportedFractionalBits(bits) => write!(f, "unsupported number of fractional bits {}", bits),
+        }
+    }
+}
+
+impl std::error::Error for SpzError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SpzError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// Number of rest coefficients per color channel for a spherical harmonics degree
+fn rest_coefficient_count(sh_degree: u8) -> usize {
+    match sh_degree {
+        0 => 0,
+        1 => 3,
+        2 => 8,
+        _ => 15,
+    }
+}
+
+/// Reads exactly `size` bytes, growing the buffer as they arrive because the sizes come from the untrusted splat count
+fn read_block<R: Read>(reader: &mut R, size: usize) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    reader.take(size as u64).read_to_end(&mut data)?;
+    if data.len() != size {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(data)
+}
+
+/// Reads a gzip compressed SPZ stream
+pub fn read_splats<R: Read>(reader: &mut R) -> Result<Vec<GpuSplat>, SpzError> {
+    let mut reader = GzDecoder::new(reader);
+    let header = read_block(&mut reader, 16)?;
+    let word = |index: usize| u32::from_le_bytes(header[index * 4..index * 4 + 4].try_into().unwrap());
+    if word(0) != MAGIC {
+        return Err(SpzError::InvalidMagic(word(0)));
+    }
+    let version = word(1);
+    if !(2..=3).contains(&version) {
+        return Err(SpzError::UnsupportedVersion(version));
+    }
+    let count = word(2) as usize;
+    let sh_degree = header[12];
+    if sh_degree > 3 {
+        return Err(SpzError::UnsupportedShDegree(sh_degree));
+    }
+    let fractional_bits = header[13];
+    if fractional_bits > 23 {
+        return Err(SpzError::UnsupportedFractionalBits(fractional_bits));
+    }
+    let rest_coefficients = rest_coefficient_count(sh_degree);
+    let rotation_size = if version >= 3 { 4 } else { 3 };
+
+    let positions = read_block(&mut reader, count * 9)?;
+    let alphas = read_block(&mut reader, count)?;
+    let colors = read_block(&mut reader, count * 3)?;
+    let scales = read_block(&mut reader, count * 3)?;
+    let rotations = read_block(&mut reader, count * rotation_size)?;
+    let sh = read_block(&mut reader, count * rest_coefficients * 3)?;
+
+    let position_scale = 1.0 / (1 << fractional_bits) as f32;
+    let axis_flip = [1.0, -1.0, -1.0];
+    let splats = (0..count)
+        .map(|index| {
+            let mut center = [0.0; 3];
+            for axis in 0..3 {
+                let bytes = &positions[(index * 3 + axis) * 3..(index * 3 + axis) * 3 + 3];
+                // Sign extend the 24 bit integer
+                let fixed = i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) >> 8;
+                center[axis] = fixed as f32 * position_scale * axis_flip[axis];
+            }
+            let [x, y, z, w] = if version >= 3 {
+                decode_smallest_three(u32::from_le_bytes(rotations[index * 4..index * 4 + 4].try_into().unwrap()))
+            } else {
+                let [x, y, z] = [0, 1, 2].map(|axis| rotations[index * 3 + axis] as f32 / 127.5 - 1.0);
+                [x, y, z, (1.0 - (x * x + y * y + z * z)).max(0.0).sqrt()]
+            };
+            let norm = (x * x + y * y + z * z + w * w).sqrt().max(f32::EPSILON);
+            let mut color_sh = [0.0; 48];
+            for channel in 0..3 {
+                color_sh[channel] = (colors[index * 3 + channel] as f32 / 255.0 - 0.5) / COLOR_SCALE;
+            }
+            for coefficient in 0..rest_coefficients {
+                for channel in 0..3 {
+                    let byte = sh[(index * rest_coefficients + coefficient) * 3 + channel];
+                    color_sh[(coefficient + 1) * 3 + channel] = (byte as f32 - 128.0) / 128.0 * SH_FLIP[coefficient];
+                }
+            }
+            GpuSplat {
+                rotation: [w / norm, x / norm, -y / norm, -z / norm],
+                center,
+                padding_a: 0.0,
+                scale: [0, 1, 2].map(|axis| (scales[index * 3 + axis] as f32 / 16.0 - 10.0).exp()),
+                alpha: alphas[index] as f32 / 255.0,
+                color_sh,
+            }
+        })
+        .collect();
+    Ok(splats)
+}
+
+/// Writes a gzip compressed SPZ stream of version 3
+///
+/// The spherical harmonics degree is the highest one which has any non-zero coefficient.
+pub fn write_splats<W: Write>(writer: &mut W, splats: &[GpuSplat]) -> io::Result<()> {
+    let sh_degree = (1..=3)
+        .rev()
+        .find(|degree| {
+            let bands = rest_coefficient_count(*degree - 1) + 1..rest_coefficient_count(*degree) + 1;
+            splats.iter().any(|splat| {
+                splat.color_sh[bands.start * 3..bands.end * 3]
+                    .iter()
+                    .any(|coefficient| *coefficient != 0.0)
+            })
+        })
+        .unwrap_or(0);
+    let rest_coefficients = rest_coefficient_count(sh_degree);
+    let quantize = |value: f32| value.round().clamp(0.0, 255.0) as u8;
+
+    let mut header = Vec::with_capacity(16);
+    header.extend_from_slice(&MAGIC.to_le_bytes());
+    header.extend_from_slice(&VERSION.to_le_bytes());
+    header.extend_from_slice(&(splats.len() as u32).to_le_bytes());
+    header.extend_from_slice(&[sh_degree, FRACTIONAL_BITS, 0, 0]);
+
+    let mut positions = Vec::with_capacity(splats.len() * 9);
+    let mut alphas = Vec::with_capacity(splats.len());
+    let mut colors = Vec::with_capacity(splats.len() * 3);
+    let mut scales = Vec::with_capacity(splats.len() * 3);
+    let mut rotations = Vec::with_capacity(splats.len() * 4);
+    let mut sh = Vec::with_capacity(splats.len() * rest_coefficients * 3);
+    let axis_flip = [1.0, -1.0, -1.0];
+    for splat in splats {
+        for (position, flip) in splat.center.iter().zip(axis_flip) {
+            let fixed = (position * flip * (1 << FRACTIONAL_BITS) as f32)
+                .round()
+                .clamp(-(1 << 23) as f32, ((1 << 23) - 1) as f32) as i32;
+            positions.extend_from_slice(&fixed.to_le_bytes()[0..3]);
+        }
+        alphas.push(quantize(splat.alpha * 255.0));
+        for channel in 0..3 {
+            colors.push(quantize((splat.color_sh[channel] * COLOR_SCALE + 0.5) * 255.0));
+        }
+        for axis in 0..3 {
+            scales.push(quantize((splat.scale[axis].max(f32::MIN_POSITIVE).ln() + 10.0) * 16.0));
+        }
+        let [w, x, y, z] = splat.rotation;
+        rotations.extend_from_slice(&encode_smallest_three([x, -y, -z, w]).to_le_bytes());
+        for (coefficient, flip) in SH_FLIP.iter().enumerate().take(rest_coefficients) {
+            // The first band keeps 5 bits, the higher bands 4 bits
+            let bucket_size = if coefficient < 3 { 8.0 } else { 16.0 };
+            for channel in 0..3 {
+                let value = splat.color_sh[(coefficient + 1) * 3 + channel] * flip;
+                let quantized = (value * 128.0 + 128.0).round();
+                sh.push(quantize(((quantized + bucket_size * 0.5) / bucket_size).floor() * bucket_size));
+            }
+        }
+    }
+
+    let mut writer = GzEncoder::new(writer, Compression::default());
+    for block in [header, positions, alphas, colors, scales, rotations, sh] {
+        writer.write_all(&block)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// Decodes a quaternion in XYZW order, the upper two bits store the index of the largest component
+fn decode_smallest_three(mut packed: u32) -> [f32; 4] {
+    const MASK: u32 = (1 << 9) - 1;
+    let largest = (packed >> 30) as usize;
+    let mut rotation = [0.0; 4];
+    let mut sum_of_squares = 0.0;
+    for index in (0..4).rev() {
+        if index != largest {
+            let magnitude = (packed & MASK) as f32 / MASK as f32 * std::f32::consts::FRAC_1_SQRT_2;
+            rotation[index] = if (packed >> 9) & 1 == 1 { -magnitude } else { magnitude };
+            sum_of_squares += rotation[index] * rotation[index];
+            packed >>= 10;
+        }
+    }
+    rotation[largest] = (1.0 - sum_of_squares).max(0.0).sqrt();
+    rotation
+}
+
+/// Encodes a quaternion in XYZW order, the largest component is made positive and omitted
+fn encode_smallest_three(rotation: [f32; 4]) -> u32 {
+    const MASK: u32 = (1 << 9) - 1;
+    let norm = rotation.iter().map(|value| value * value).sum::<f32>().sqrt().max(f32::EPSILON);
+    let rotation = rotation.map(|value| value / norm);
+    let largest = (0..4).max_by(|a, b| rotation[*a].abs().total_cmp(&rotation[*b].abs())).unwrap();
+    let negate = rotation[largest] < 0.0;
+    let mut packed = largest as u32;
+    for (index, value) in rotation.iter().enumerate() {
+        if index != largest {
+            let sign = ((*value < 0.0) ^ negate) as u32;
+            let magnitude = (MASK as f32 * value.abs() / std::f32::consts::FRAC_1_SQRT_2 + 0.5).floor() as u32;
+            packed = packed << 10 | sign << 9 | magnitude.min(MASK);
+        }
+    }
+    packed
+}
@@ -0,0 +1,148 @@
+//! Specialization of shaders.wgsl
+//!
+//! The shader refers to constants like `RADIX_BASE` or `USE_DEPTH_SORTING` which it does not declare itself.
+//! They are derived from a [Configuration] and prepended to the source as `const` declarations.
+use crate::renderer::{Configuration, DepthSorting};
+use std::{collections::HashMap, sync::Arc};
+
+/// Sizes of the GPU radix sort, derived from [Configuration::radix_bits_per_digit] and [Configuration::max_splat_count]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SortingLayout {
+    pub radix_bits_per_digit: usize,
+    /// Number of passes of radix sort C, one per digit of the 32 bit keys
+    pub radix_digit_places: usize,
+    pub radix_base: usize,
+    pub entries_per_invocation_a: usize,
+    pub entries_per_invocation_c: usize,
+    pub workgroup_invocations_a: usize,
+    pub workgroup_invocations_c: usize,
+    pub workgroup_entries_a: usize,
+    pub workgroup_entries_c: usize,
+    /// Number of workgroups of radix sort C needed for [Configuration::max_splat_count]
+    pub max_tile_count_c: usize,
+    /// Size of `SortingGlobal` in shaders.wgsl in bytes
+    pub sorting_buffer_size: usize,
+}
+
+impl SortingLayout {
+    /// Panics if [Configuration::validate] fails
+    pub fn new(config: &Configuration) -> Self {
+        if let Err(error) = config.validate() {
+            panic!("{}", error);
+        }
+        let radix_bits_per_digit = config.radix_bits_per_digit;
+        let radix_digit_places = 32 / radix_bits_per_digit;
+        let radix_base = 1 << radix_bits_per_digit;
+        let entries_per_invocation_a = 4;
+        let entries_per_invocation_c = 4;
+        let workgroup_invocations_a = radix_base * radix_digit_places;
+        let workgroup_invocations_c = radix_base;
+        let workgroup_entries_a = workgroup_invocations_a * entries_per_invocation_a;
+        let workgroup_entries_c = workgroup_invocations_c * entries_per_invocation_c;
+        let max_tile_count_c = config.max_splat_count.div_ceil(workgroup_entries_c).max(1);
+        // Status counters and digit histogram, followed by the indirect draw arguments and the assignment counter
+        let sorting_buffer_size = (radix_base * (radix_digit_places + max_tile_count_c) + 5) * std::mem::size_of::<u32>();
+        Self {
+            radix_bits_per_digit,
+            radix_digit_places,
+            radix_base,
+            entries_per_invocation_a,
+            entries_per_invocation_c,
+            workgroup_invocations_a,
+            workgroup_invocations_c,
+            workgroup_entries_a,
+            workgroup_entries_c,
+            max_tile_count_c,
+            sorting_buffer_size,
+        }
+    }
+}
+
+/// Everything of a [Configuration] which is baked into the shader source
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ShaderKey {
+    pub sorting_layout: SortingLayout,
+    pub spherical_harmonics_order: usize,
+    /// [DepthSorting::GpuIndirectDraw]
+    pub use_indirect_draw: bool,
+    /// Anything but [DepthSorting::None], the shader does not care where the sorting takes place
+    pub use_depth_sorting: bool,
+    pub use_covariance_for_scale: bool,
+    pub use_unaligned_rectangles: bool,
+}
+
+impl ShaderKey {
+    pub fn new(config: &Configuration) -> Self {
+        Self {
+            sorting_layout: SortingLayout::new(config),
+            spherical_harmonics_order: config.spherical_harmonics_order,
+            use_indirect_draw: config.depth_sorting == DepthSorting::GpuIndirectDraw,
+            use_depth_sorting: config.depth_sorting != DepthSorting::None,
+            use_covariance_for_scale: config.use_covariance_for_scale,
+            use_unaligned_rectangles: config.use_unaligned_rectangles,
+        }
+    }
+
+    /// WGSL source of shaders.wgsl with all of its constants declared
+    pub fn source(&self) -> String {
+        let layout = &self.sorting_layout;
+        let integers = [
+            ("RADIX_BITS_PER_DIGIT", layout.radix_bits_per_digit),
+            ("RADIX_DIGIT_PLACES", layout.radix_digit_places),
+            ("RADIX_BASE", layout.radix_base),
+            ("ENTRIES_PER_INVOCATION_A", layout.entries_per_invocation_a),
+            ("ENTRIES_PER_INVOCATION_C", layout.entries_per_invocation_c),
+            ("WORKGROUP_INVOCATIONS_C", layout.workgroup_invocations_c),
+            ("WORKGROUP_ENTRIES_C", layout.workgroup_entries_c),
+            ("MAX_TILE_COUNT_C", layout.max_tile_count_c),
+            ("SPHERICAL_HARMONICS_ORDER", self.spherical_harmonics_order),
+        ];
+        let booleans = [
+            ("USE_INDIRECT_DRAW", self.use_indirect_draw),
+            ("USE_DEPTH_SORTING", self.use_depth_sorting),
+            ("USE_COVARIANCE_FOR_SCALE", self.use_covariance_for_scale),
+            ("USE_UNALIGNED_RECTANGLES", self.use_unaligned_rectangles),
+        ];
+        let mut source = String::new();
+        for (name, value) in integers {
+            source += &format!("const {}: u32 = {}u;\n", name, value);
+        }
+        for (name, value) in booleans {
+            source += &format!("const {}: bool = {};\n", name, value);
+        }
+        // naga does not evaluate constants in workgroup sizes yet, so these are substituted by literals
+        for line in include_str!("shaders.wgsl").lines() {
+            let mut line = line.to_string();
+            if line.contains("@workgroup_size(") {
+                for (name, value) in integers {
+                    line = line.replace(name, &value.to_string());
+                }
+            }
+            source += &line;
+            source.push('\n');
+        }
+        source
+    }
+}
+
+/// Compiles each variant of shaders.wgsl only once
+#[derive(Default)]
+pub struct ShaderCache {
+    modules: HashMap<ShaderKey, Arc<wgpu::ShaderModule>>,
+}
+
+impl ShaderCache {
+    /// Returns the shader module specialized for `config`, compiling it on first use
+    pub fn get(&mut self, device: &wgpu::Device, config: &Configuration) -> Arc<wgpu::ShaderModule> {
+        let key = ShaderKey::new(config);
+        self.modules
+            .entry(key)
+            .or_insert_with(|| {
+                Arc::new(device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: Some("Splat Shader"),
+                    source: wgpu::ShaderSource::Wgsl(key.source().into()),
+                }))
+            })
+            .clone()
+    }
+}
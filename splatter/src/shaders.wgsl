@@ -8,6 +8,10 @@ struct Uniforms {
     ellipse_size_bias: f32,
     ellipse_margin: f32,
     splat_scale: f32,
+    splat_count: u32,
+    model_count: u32,
+    // Maps the view space depth z to the depth buffer value x + y / z
+    depth_projection: vec2<f32>,
 }
 struct DrawIndirect {
     vertex_count: u32,
@@ -33,6 +37,13 @@ struct Splat {
     alpha: f32,
     colorSH: array<f32, 48>,
 }
+struct Model {
+    transform: mat4x4<f32>,
+    scale: f32,
+    splat_offset: u32,
+    splat_scale: f32,
+    buffer_offset: u32,
+}
 @group(0) @binding(0) var<uniform> uniforms: Uniforms;
 @group(0) @binding(1) var<uniform> sorting_pass_index: u32;
 @group(0) @binding(2) var<storage, read_write> sorting: SortingGlobal;
@@ -40,6 +51,7 @@ struct Splat {
 @group(0) @binding(4) var<storage, read_write> output_entries: array<Entry>;
 @group(0) @binding(5) var<storage, read> sorted_entries: array<Entry>;
 @group(0) @binding(6) var<storage> splats: array<Splat>;
+@group(0) @binding(7) var<storage> models: array<Model>;
 
 fn screenToClipSpace(screen_space_pos: vec2<f32>) -> vec2<f32> {
     var result = ((screen_space_pos.xy / vec2<f32>(uniforms.image_size)) - vec2<f32>(0.5));
@@ -60,6 +72,35 @@ fn isInFrustum(clip_space_pos: vec3<f32>) -> bool {
     return abs(clip_space_pos.x) < uniforms.frustum_culling_tolerance && abs(clip_space_pos.y) < uniforms.frustum_culling_tolerance && abs(clip_space_pos.z - 0.5) < 0.5;
 }
 
+// Finds the model a splat belongs to, models are ordered by their splat_offset
+fn modelIndexOfSplat(splat_index: u32) -> u32 {
+    var low = 0u;
+    var high = uniforms.model_count;
+    while(high - low > 1u) {
+        let middle = (low + high) / 2u;
+        if(models[middle].splat_offset <= splat_index) {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    return low;
+}
+
+// The splats of a model start at its buffer_offset in splats, which can be shared with other views
+fn gatheredSplatIndex(model_index: u32, splat_index: u32) -> u32 {
+    return splat_index - models[model_index].splat_offset + models[model_index].buffer_offset;
+}
+
+fn modelToWorldSpace(model_index: u32, model_pos: vec3<f32>) -> vec3<f32> {
+    return (models[model_index].transform * vec4<f32>(model_pos, 1.0)).xyz;
+}
+
+fn modelRotation(model_index: u32) -> mat3x3<f32> {
+    let transform = models[model_index].transform;
+    return mat3x3<f32>(transform.x.xyz, transform.y.xyz, transform.z.xyz) * (1.0 / models[model_index].scale);
+}
+
 fn quatToMat(p: vec4<f32>) -> mat3x3<f32> {
   var q = p * sqrt(2.0);
   var yy = q.y * q.y;
@@ -96,9 +137,9 @@ fn quatToMat(p: vec4<f32>) -> mat3x3<f32> {
         This however is an edge case that only happens when the ellipsoid intersects with the view plane and
         can probably be ignored as one would clip away such ellipsoids anyway.
 */
-fn projectedCovarianceOfEllipsoid(scale: vec3<f32>, rotation: vec4<f32>, translation: vec3<f32>) -> mat3x3<f32> {
+fn projectedCovarianceOfEllipsoid(scale: vec3<f32>, rotation: mat3x3<f32>, translation: vec3<f32>) -> mat3x3<f32> {
     let camera_matrix = mat3x3<f32>(uniforms.camera_matrix.x.xyz, uniforms.camera_matrix.y.xyz, uniforms.camera_matrix.z.xyz);
-    var transform = quatToMat(rotation);
+    var transform = rotation;
     transform.x *= scale.x;
     transform.y *= scale.y;
     transform.z *= scale.z;
@@ -122,9 +163,9 @@ fn projectedCovarianceOfEllipsoid(scale: vec3<f32>, rotation: vec4<f32>, transla
     Then find the intersection between that bounding cone and the view plane. The resulting conic section is the correct contour in 2D,
     formulated as an algebraic / implicit curve: 0 = M.x.x * x^2 + M.y.y * y^2 + M.x.y * 2.0 * x * y + M.x.z * 2.0 * x + M.y.z * 2.0 * y + M.z.z
 */
-fn projectedContourOfEllipsoid(scale: vec3<f32>, rotation: vec4<f32>, translation: vec3<f32>) -> mat3x3<f32> {
+fn projectedContourOfEllipsoid(scale: vec3<f32>, rotation: mat3x3<f32>, translation: vec3<f32>) -> mat3x3<f32> {
     let camera_matrix = mat3x3<f32>(uniforms.camera_matrix.x.xyz, uniforms.camera_matrix.y.xyz, uniforms.camera_matrix.z.xyz);
-    var transform = quatToMat(rotation);
+    var transform = rotation;
     transform.x /= scale.x;
     transform.y /= scale.y;
     transform.z /= scale.z;
@@ -265,30 +306,30 @@ const shc = array<f32, 16>(
 	-0.5900435899266435,
 );
 
-fn sphericalHarmonicsLookup(ray_direction: vec3<f32>, splat_index: u32) -> vec3<f32> {
+fn sphericalHarmonicsLookup(ray_direction: vec3<f32>, gathered_index: u32) -> vec3<f32> {
     var ray_direction_squared = ray_direction * ray_direction;
     var color = vec3<f32>(0.5);
-    color += shc[ 0] * vec3<f32>(splats[splat_index].colorSH[ 0], splats[splat_index].colorSH[ 1], splats[splat_index].colorSH[ 2]);
+    color += shc[ 0] * vec3<f32>(splats[gathered_index].colorSH[ 0], splats[gathered_index].colorSH[ 1], splats[gathered_index].colorSH[ 2]);
     if(SPHERICAL_HARMONICS_ORDER > 0u) {
-        color += shc[ 1] * vec3<f32>(splats[splat_index].colorSH[ 3], splats[splat_index].colorSH[ 4], splats[splat_index].colorSH[ 5]) * ray_direction.y;
-        color += shc[ 2] * vec3<f32>(splats[splat_index].colorSH[ 6], splats[splat_index].colorSH[ 7], splats[splat_index].colorSH[ 8]) * ray_direction.z;
-        color += shc[ 3] * vec3<f32>(splats[splat_index].colorSH[ 9], splats[splat_index].colorSH[10], splats[splat_index].colorSH[11]) * ray_direction.x;
+        color += shc[ 1] * vec3<f32>(splats[gathered_index].colorSH[ 3], splats[gathered_index].colorSH[ 4], splats[gathered_index].colorSH[ 5]) * ray_direction.y;
+        color += shc[ 2] * vec3<f32>(splats[gathered_index].colorSH[ 6], splats[gathered_index].colorSH[ 7], splats[gathered_index].colorSH[ 8]) * ray_direction.z;
+        color += shc[ 3] * vec3<f32>(splats[gathered_index].colorSH[ 9], splats[gathered_index].colorSH[10], splats[gathered_index].colorSH[11]) * ray_direction.x;
     }
     if(SPHERICAL_HARMONICS_ORDER > 1u) {
-        color += shc[ 4] * vec3<f32>(splats[splat_index].colorSH[12], splats[splat_index].colorSH[13], splats[splat_index].colorSH[14]) * ray_direction.x * ray_direction.y;
-        color += shc[ 5] * vec3<f32>(splats[splat_index].colorSH[15], splats[splat_index].colorSH[16], splats[splat_index].colorSH[17]) * ray_direction.y * ray_direction.z;
-        color += shc[ 6] * vec3<f32>(splats[splat_index].colorSH[18], splats[splat_index].colorSH[19], splats[splat_index].colorSH[20]) * (2.0 * ray_direction_squared.z - ray_direction_squared.x - ray_direction_squared.y);
-        color += shc[ 7] * vec3<f32>(splats[splat_index].colorSH[21], splats[splat_index].colorSH[22], splats[splat_index].colorSH[23]) * ray_direction.x * ray_direction.z;
-        color += shc[ 8] * vec3<f32>(splats[splat_index].colorSH[24], splats[splat_index].colorSH[25], splats[splat_index].colorSH[26]) * (ray_direction_squared.x - ray_direction_squared.y);
+        color += shc[ 4] * vec3<f32>(splats[gathered_index].colorSH[12], splats[gathered_index].colorSH[13], splats[gathered_index].colorSH[14]) * ray_direction.x * ray_direction.y;
+        color += shc[ 5] * vec3<f32>(splats[gathered_index].colorSH[15], splats[gathered_index].colorSH[16], splats[gathered_index].colorSH[17]) * ray_direction.y * ray_direction.z;
+        color += shc[ 6] * vec3<f32>(splats[gathered_index].colorSH[18], splats[gathered_index].colorSH[19], splats[gathered_index].colorSH[20]) * (2.0 * ray_direction_squared.z - ray_direction_squared.x - ray_direction_squared.y);
+        color += shc[ 7] * vec3<f32>(splats[gathered_index].colorSH[21], splats[gathered_index].colorSH[22], splats[gathered_index].colorSH[23]) * ray_direction.x * ray_direction.z;
+        color += shc[ 8] * vec3<f32>(splats[gathered_index].colorSH[24], splats[gathered_index].colorSH[25], splats[gathered_index].colorSH[26]) * (ray_direction_squared.x - ray_direction_squared.y);
     }
     if(SPHERICAL_HARMONICS_ORDER > 2u) {
-        color += shc[ 9] * vec3<f32>(splats[splat_index].colorSH[27], splats[splat_index].colorSH[28], splats[splat_index].colorSH[29]) * ray_direction.y * (3.0 * ray_direction_squared.x - ray_direction_squared.y);
-        color += shc[10] * vec3<f32>(splats[splat_index].colorSH[30], splats[splat_index].colorSH[31], splats[splat_index].colorSH[32]) * ray_direction.x * ray_direction.y * ray_direction.z;
-        color += shc[11] * vec3<f32>(splats[splat_index].colorSH[33], splats[splat_index].colorSH[34], splats[splat_index].colorSH[35]) * ray_direction.y * (4.0 * ray_direction_squared.z - ray_direction_squared.x - ray_direction_squared.y);
-        color += shc[12] * vec3<f32>(splats[splat_index].colorSH[36], splats[splat_index].colorSH[37], splats[splat_index].colorSH[38]) * ray_direction.z * (2.0 * ray_direction_squared.z - 3.0 * ray_direction_squared.x - 3.0 * ray_direction_squared.y);
-        color += shc[13] * vec3<f32>(splats[splat_index].colorSH[39], splats[splat_index].colorSH[40], splats[splat_index].colorSH[41]) * ray_direction.x * (4.0 * ray_direction_squared.z - ray_direction_squared.x - ray_direction_squared.y);
-        color += shc[14] * vec3<f32>(splats[splat_index].colorSH[42], splats[splat_index].colorSH[43], splats[splat_index].colorSH[44]) * ray_direction.z * (ray_direction_squared.x - ray_direction_squared.y);
-        color += shc[15] * vec3<f32>(splats[splat_index].colorSH[45], splats[splat_index].colorSH[46], splats[splat_index].colorSH[47]) * ray_direction.x * (ray_direction_squared.x - 3.0 * ray_direction_squared.y);
+        color += shc[ 9] * vec3<f32>(splats[gathered_index].colorSH[27], splats[gathered_index].colorSH[28], splats[gathered_index].colorSH[29]) * ray_direction.y * (3.0 * ray_direction_squared.x - ray_direction_squared.y);
+        color += shc[10] * vec3<f32>(splats[gathered_index].colorSH[30], splats[gathered_index].colorSH[31], splats[gathered_index].colorSH[32]) * ray_direction.x * ray_direction.y * ray_direction.z;
+        color += shc[11] * vec3<f32>(splats[gathered_index].colorSH[33], splats[gathered_index].colorSH[34], splats[gathered_index].colorSH[35]) * ray_direction.y * (4.0 * ray_direction_squared.z - ray_direction_squared.x - ray_direction_squared.y);
+        color += shc[12] * vec3<f32>(splats[gathered_index].colorSH[36], splats[gathered_index].colorSH[37], splats[gathered_index].colorSH[38]) * ray_direction.z * (2.0 * ray_direction_squared.z - 3.0 * ray_direction_squared.x - 3.0 * ray_direction_squared.y);
+        color += shc[13] * vec3<f32>(splats[gathered_index].colorSH[39], splats[gathered_index].colorSH[40], splats[gathered_index].colorSH[41]) * ray_direction.x * (4.0 * ray_direction_squared.z - ray_direction_squared.x - ray_direction_squared.y);
+        color += shc[14] * vec3<f32>(splats[gathered_index].colorSH[42], splats[gathered_index].colorSH[43], splats[gathered_index].colorSH[44]) * ray_direction.z * (ray_direction_squared.x - ray_direction_squared.y);
+        color += shc[15] * vec3<f32>(splats[gathered_index].colorSH[45], splats[gathered_index].colorSH[46], splats[gathered_index].colorSH[47]) * ray_direction.x * (ray_direction_squared.x - 3.0 * ray_direction_squared.y);
     }
     return color;
 }
@@ -312,14 +353,15 @@ fn radixSortA(
     let start_entry_index = thread_index * ENTRIES_PER_INVOCATION_A;
     let end_entry_index = start_entry_index + ENTRIES_PER_INVOCATION_A;
     for(var entry_index = start_entry_index; entry_index < end_entry_index; entry_index += 1u) {
-        if(entry_index >= arrayLength(&splats)) {
+        if(entry_index >= uniforms.splat_count) {
             continue;
         }
         var key: u32 = 0xFFFFFFFFu; // Stream compaction for frustum culling
-        let clip_space_pos = worldToClipSpace(splats[entry_index].center);
+        let model_index = modelIndexOfSplat(entry_index);
+        let clip_space_pos = worldToClipSpace(modelToWorldSpace(model_index, splats[gatheredSplatIndex(model_index, entry_index)].center));
         if(isInFrustum(clip_space_pos.xyz)) {
-            // key = bitcast<u32>(clip_space_pos.z);
-            key = u32(clip_space_pos.z * 0xFFFF.0) << 16u;
+            // Back to front, the depth is inverted so that culled splats still sort last
+            key = u32((1.0 - clip_space_pos.z) * 0xFFFF.0) << 16u;
             key |= u32((clip_space_pos.x * 0.5 + 0.5) * 0xFF.0) << 8u;
             key |= u32((clip_space_pos.y * 0.5 + 0.5) * 0xFF.0);
         }
@@ -414,7 +456,7 @@ fn radixSortC(
     let assignment = sorting_shared_c.entries[0];
     let global_entry_offset = assignment * WORKGROUP_ENTRIES_C;
     // TODO: Specialize end shader
-    if(gl_LocalInvocationID.x == 0u && assignment * WORKGROUP_ENTRIES_C + WORKGROUP_ENTRIES_C >= arrayLength(&splats)) {
+    if(gl_LocalInvocationID.x == 0u && assignment * WORKGROUP_ENTRIES_C + WORKGROUP_ENTRIES_C >= uniforms.splat_count) {
         // Last workgroup resets the assignment number for the next pass
         sorting.assignment_counter = 0u;
     }
@@ -455,7 +497,7 @@ fn radixSortC(
         }
     }
     atomicStore(&sorting.status_counters[assignment][gl_LocalInvocationID.x], 0x80000000u | (global_digit_count + local_digit_count));
-    if(sorting_pass_index == RADIX_DIGIT_PLACES - 1u && gl_LocalInvocationID.x == WORKGROUP_INVOCATIONS_C - 2u && global_entry_offset + WORKGROUP_ENTRIES_C >= arrayLength(&splats)) {
+    if(sorting_pass_index == RADIX_DIGIT_PLACES - 1u && gl_LocalInvocationID.x == WORKGROUP_INVOCATIONS_C - 2u && global_entry_offset + WORKGROUP_ENTRIES_C >= uniforms.splat_count) {
         sorting.draw_indirect.vertex_count = 4u;
         sorting.draw_indirect.instance_count = global_digit_count + local_digit_count;
     }
@@ -500,7 +542,8 @@ fn radixSortC(
 struct VertexOutput {
     @builtin(position) gl_Position: vec4<f32>,
     @location(0) @interpolate(flat) color: vec4<f32>,
-    @location(1) @interpolate(linear) gl_TexCoord: vec2<f32>,
+    // Quads are flat with w = 1, so this does not need noperspective, which GL backends lack
+    @location(1) gl_TexCoord: vec2<f32>,
     // @location(2) @interpolate(flat) splat_index: u32,
 }
 
@@ -520,22 +563,32 @@ fn vertex(
         discard_quad = sorted_entries[gl_InstanceID][0] == 0xFFFFFFFFu;
     } else {
         splat_index = gl_InstanceID;
-        discard_quad = !isInFrustum(worldToClipSpace(splats[splat_index].center).xyz);
+        let model_index = modelIndexOfSplat(splat_index);
+        discard_quad = !isInFrustum(worldToClipSpace(modelToWorldSpace(model_index, splats[gatheredSplatIndex(model_index, splat_index)].center)).xyz);
     }
     if(discard_quad) {
         stage_out.gl_Position = vec4<f32>(0.0);
         return stage_out;
     }
     // stage_out.splat_index = splat_index;
-    let world_position = splats[splat_index].center;
-    let ray_direction = normalize(world_position - uniforms.camera_matrix.w.xyz);
-    stage_out.color = vec4<f32>(sphericalHarmonicsLookup(ray_direction, splat_index), splats[splat_index].alpha);
-    let M = projectedContourOfEllipsoid(splats[splat_index].scale * uniforms.splat_scale, splats[splat_index].rotation, world_position);
+    let model_index = modelIndexOfSplat(splat_index);
+    let gathered_index = gatheredSplatIndex(model_index, splat_index);
+    let world_position = modelToWorldSpace(model_index, splats[gathered_index].center);
+    let model_rotation = modelRotation(model_index);
+    let world_rotation = model_rotation * quatToMat(splats[gathered_index].rotation);
+    let world_scale = splats[gathered_index].scale * models[model_index].scale * models[model_index].splat_scale * uniforms.splat_scale;
+    // The whole quad is placed at the depth of the splat center
+    let view_depth = (uniforms.view_matrix * vec4<f32>(world_position, 1.0)).z;
+    let depth = clamp(uniforms.depth_projection.x + uniforms.depth_projection.y / view_depth, 0.0, 1.0);
+    // The spherical harmonics are defined in model space
+    let ray_direction = normalize(world_position - uniforms.camera_matrix.w.xyz) * model_rotation;
+    stage_out.color = vec4<f32>(sphericalHarmonicsLookup(ray_direction, gathered_index), splats[gathered_index].alpha);
+    let M = projectedContourOfEllipsoid(world_scale, world_rotation, world_position);
     let translation = extractTranslationOfEllipse(M);
     let rotation = extractRotationOfEllipse(M);
     var semi_axes: vec2<f32>;
     if(USE_COVARIANCE_FOR_SCALE) {
-        let covariance = projectedCovarianceOfEllipsoid(splats[splat_index].scale * uniforms.splat_scale, splats[splat_index].rotation, world_position);
+        let covariance = projectedCovarianceOfEllipsoid(world_scale, world_rotation, world_position);
         semi_axes = extractScaleOfCovariance(covariance);
     } else {
         semi_axes = extractScaleOfEllipse(M, translation, rotation);
@@ -558,7 +611,7 @@ fn vertex(
             vec3<f32>(transformation.z, 1.0),
         );
         stage_out.gl_TexCoord = quad_vertices[gl_VertexID] * uniforms.ellipse_margin;
-        stage_out.gl_Position = vec4<f32>((T * vec3<f32>(stage_out.gl_TexCoord, 1.0)).xy / uniforms.view_size, 0.0, 1.0);
+        stage_out.gl_Position = vec4<f32>((T * vec3<f32>(stage_out.gl_TexCoord, 1.0)).xy / uniforms.view_size, depth, 1.0);
     } else {
         let inverse = mat2x2<f32>(
             transformation.y.y, -transformation.x.y,
@@ -566,7 +619,7 @@ fn vertex(
         ) * (1.0 / (transformation.x.x * transformation.y.y - transformation.x.y * transformation.y.x));
         let radius = sqrt(max(dot(transformation.x, transformation.x), dot(transformation.y, transformation.y)));
         stage_out.gl_TexCoord = quad_vertices[gl_VertexID] * radius * uniforms.ellipse_margin;
-        stage_out.gl_Position = vec4<f32>((transformation.z + stage_out.gl_TexCoord) / uniforms.view_size, 0.0, 1.0);
+        stage_out.gl_Position = vec4<f32>((transformation.z + stage_out.gl_TexCoord) / uniforms.view_size, depth, 1.0);
         stage_out.gl_TexCoord = inverse * stage_out.gl_TexCoord;
     }
     return stage_out;
@@ -590,3 +643,292 @@ fn fragment(
     stage_out.gl_Color = vec4<f32>(stage_in.color.rgb * alpha, alpha);
     return stage_out;
 }
+
+// Fragments of at least this opacity write their depth, which approximates the median depth of dense surfaces
+const DEPTH_ALPHA_THRESHOLD: f32 = 0.5;
+
+@fragment
+fn depth_fragment(
+    stage_in: VertexOutput,
+) -> FragmentOutput {
+    var stage_out: FragmentOutput;
+    let power = dot(stage_in.gl_TexCoord, stage_in.gl_TexCoord);
+    if(stage_in.color.a * exp(-0.5 * power) < DEPTH_ALPHA_THRESHOLD) {
+        discard;
+    }
+    // The color is masked out by the pipeline, only the depth of the quad is written
+    stage_out.gl_Color = vec4<f32>(0.0);
+    return stage_out;
+}
+
+// Tile-based compute rasterizer of DepthSorting::GpuTiled, an alternative to the vertex and fragment shaders above
+//
+// Splats are projected and counted per 16x16 pixel tile, the counts are scanned into offsets, the splats are binned
+// into per tile lists, and each tile sorts its list front to back and composites it with early termination.
+
+const TILE_SIZE: u32 = 16u;
+
+struct ProjectedSplat {
+    inverse_transformation: mat2x2<f32>,
+    translation: vec2<f32>,
+    // Inclusive range of tiles the splat overlaps, x in the lower and y in the upper 16 bits
+    tile_min: u32,
+    tile_max: u32,
+    color: vec4<f32>,
+    depth: f32,
+}
+struct Tile {
+    splat_count: atomic<u32>,
+    entry_offset: u32,
+    entry_cursor: atomic<u32>,
+    padding: u32,
+}
+@group(1) @binding(0) var<storage, read_write> projected_splats: array<ProjectedSplat>;
+@group(1) @binding(1) var<storage, read_write> tiles: array<Tile>;
+@group(1) @binding(2) var<storage, read_write> tile_entries: array<Entry>;
+@group(1) @binding(3) var<storage, read_write> tile_pixels: array<vec2<u32>>;
+// Aliases tile_pixels for the blit, which may only read it
+@group(1) @binding(4) var<storage, read> composited_pixels: array<vec2<u32>>;
+
+fn tileCount() -> vec2<u32> {
+    return (uniforms.image_size + vec2<u32>(TILE_SIZE - 1u)) / TILE_SIZE;
+}
+
+// Also works where the compiler assumes that there are no NaNs
+fn isFinite(value: f32) -> bool {
+    return (bitcast<u32>(value) & 0x7F800000u) != 0x7F800000u;
+}
+
+@compute @workgroup_size(256)
+fn tileProject(
+    @builtin(global_invocation_id) gl_GlobalInvocationID: vec3<u32>,
+) {
+    let splat_index = gl_GlobalInvocationID.x;
+    if(splat_index >= uniforms.splat_count) {
+        return;
+    }
+    // An empty range of tiles, unless the splat turns out to be visible
+    projected_splats[splat_index].tile_min = 0xFFFFFFFFu;
+    projected_splats[splat_index].tile_max = 0u;
+    let model_index = modelIndexOfSplat(splat_index);
+    let gathered_index = gatheredSplatIndex(model_index, splat_index);
+    let world_position = modelToWorldSpace(model_index, splats[gathered_index].center);
+    if(!isInFrustum(worldToClipSpace(world_position).xyz)) {
+        return;
+    }
+    let model_rotation = modelRotation(model_index);
+    let world_rotation = model_rotation * quatToMat(splats[gathered_index].rotation);
+    let world_scale = splats[gathered_index].scale * models[model_index].scale * models[model_index].splat_scale * uniforms.splat_scale;
+    let ray_direction = normalize(world_position - uniforms.camera_matrix.w.xyz) * model_rotation;
+    let M = projectedContourOfEllipsoid(world_scale, world_rotation, world_position);
+    let translation = extractTranslationOfEllipse(M);
+    let rotation = extractRotationOfEllipse(M);
+    var semi_axes: vec2<f32>;
+    if(USE_COVARIANCE_FOR_SCALE) {
+        let covariance = projectedCovarianceOfEllipsoid(world_scale, world_rotation, world_position);
+        semi_axes = extractScaleOfCovariance(covariance);
+    } else {
+        semi_axes = extractScaleOfEllipse(M, translation, rotation);
+    }
+    let transformation = mat2x2<f32>(
+        vec2<f32>(rotation.y, -rotation.x) * (uniforms.ellipse_size_bias + semi_axes.x),
+        vec2<f32>(rotation.x, rotation.y) * (uniforms.ellipse_size_bias + semi_axes.y),
+    );
+    let determinant = transformation.x.x * transformation.y.y - transformation.x.y * transformation.y.x;
+    // Bounding box of the rotated rectangle the rasterizer would draw, in pixels
+    let extent = (abs(transformation.x) + abs(transformation.y)) * uniforms.ellipse_margin / uniforms.view_size * 0.5 * vec2<f32>(uniforms.image_size);
+    let center = clipToScreenSpace(translation / uniforms.view_size);
+    if(determinant == 0.0 || !isFinite(determinant) || !isFinite(extent.x) || !isFinite(extent.y) || !isFinite(center.x) || !isFinite(center.y)) {
+        return;
+    }
+    let tile_count = tileCount();
+    let lower = (center - extent) / f32(TILE_SIZE);
+    let upper = (center + extent) / f32(TILE_SIZE);
+    if(any(upper < vec2<f32>(0.0)) || any(lower >= vec2<f32>(tile_count))) {
+        return;
+    }
+    let tile_min = vec2<u32>(max(lower, vec2<f32>(0.0)));
+    let tile_max = min(vec2<u32>(upper), tile_count - vec2<u32>(1u));
+    projected_splats[splat_index].inverse_transformation = mat2x2<f32>(
+        transformation.y.y, -transformation.x.y,
+        -transformation.y.x, transformation.x.x,
+    ) * (1.0 / determinant);
+    projected_splats[splat_index].translation = translation;
+    projected_splats[splat_index].tile_min = tile_min.x | (tile_min.y << 16u);
+    projected_splats[splat_index].tile_max = tile_max.x | (tile_max.y << 16u);
+    projected_splats[splat_index].color = vec4<f32>(sphericalHarmonicsLookup(ray_direction, gathered_index), splats[gathered_index].alpha);
+    projected_splats[splat_index].depth = (uniforms.view_matrix * vec4<f32>(world_position, 1.0)).z;
+    for(var y = tile_min.y; y <= tile_max.y; y += 1u) {
+        for(var x = tile_min.x; x <= tile_max.x; x += 1u) {
+            atomicAdd(&tiles[y * tile_count.x + x].splat_count, 1u);
+        }
+    }
+}
+
+var<workgroup> tile_scan: array<u32, 256>;
+
+// Turns the splat counts of all tiles into offsets of their lists, in a single workgroup
+@compute @workgroup_size(256)
+fn tileScan(
+    @builtin(local_invocation_index) gl_LocalInvocationIndex: u32,
+) {
+    let tile_count = tileCount();
+    let total_tile_count = tile_count.x * tile_count.y;
+    let tiles_per_invocation = (total_tile_count + 255u) / 256u;
+    let begin = min(gl_LocalInvocationIndex * tiles_per_invocation, total_tile_count);
+    let end = min(begin + tiles_per_invocation, total_tile_count);
+    var sum = 0u;
+    for(var tile_index = begin; tile_index < end; tile_index += 1u) {
+        sum += atomicLoad(&tiles[tile_index].splat_count);
+    }
+    tile_scan[gl_LocalInvocationIndex] = sum;
+    for(var stride = 1u; stride < 256u; stride <<= 1u) {
+        workgroupBarrier();
+        var value = tile_scan[gl_LocalInvocationIndex];
+        if(gl_LocalInvocationIndex >= stride) {
+            value += tile_scan[gl_LocalInvocationIndex - stride];
+        }
+        workgroupBarrier();
+        tile_scan[gl_LocalInvocationIndex] = value;
+    }
+    var offset = tile_scan[gl_LocalInvocationIndex] - sum;
+    for(var tile_index = begin; tile_index < end; tile_index += 1u) {
+        tiles[tile_index].entry_offset = offset;
+        atomicStore(&tiles[tile_index].entry_cursor, offset);
+        offset += atomicLoad(&tiles[tile_index].splat_count);
+    }
+    // The extra tile after the last one tells the CPU how many entries were needed
+    if(gl_LocalInvocationIndex == 255u) {
+        tiles[total_tile_count].entry_offset = tile_scan[255u];
+    }
+}
+
+@compute @workgroup_size(256)
+fn tileBin(
+    @builtin(global_invocation_id) gl_GlobalInvocationID: vec3<u32>,
+) {
+    let splat_index = gl_GlobalInvocationID.x;
+    if(splat_index >= uniforms.splat_count) {
+        return;
+    }
+    let tile_count = tileCount();
+    let tile_min = projected_splats[splat_index].tile_min;
+    let tile_max = projected_splats[splat_index].tile_max;
+    // The view space depth is positive, so its bits are ordered like the floats
+    let key = bitcast<u32>(projected_splats[splat_index].depth);
+    for(var y = tile_min >> 16u; y <= tile_max >> 16u; y += 1u) {
+        for(var x = tile_min & 0xFFFFu; x <= (tile_max & 0xFFFFu); x += 1u) {
+            let slot = atomicAdd(&tiles[y * tile_count.x + x].entry_cursor, 1u);
+            // Entries which exceed the capacity are dropped, the capacity grows for the following frames
+            if(slot < arrayLength(&tile_entries)) {
+                tile_entries[slot] = Entry(key, splat_index);
+            }
+        }
+    }
+}
+
+var<workgroup> tile_entry_range: vec2<u32>;
+
+fn isEntryLess(a: Entry, b: Entry) -> bool {
+    return a.key < b.key || (a.key == b.key && a.value < b.value);
+}
+
+// One workgroup per tile and one invocation per pixel
+@compute @workgroup_size(16, 16)
+fn tileRasterize(
+    @builtin(workgroup_id) gl_WorkGroupID: vec3<u32>,
+    @builtin(local_invocation_id) gl_LocalInvocationID: vec3<u32>,
+    @builtin(local_invocation_index) gl_LocalInvocationIndex: u32,
+) {
+    let tile_count = tileCount();
+    let tile_index = gl_WorkGroupID.y * tile_count.x + gl_WorkGroupID.x;
+    if(gl_LocalInvocationIndex == 0u) {
+        let begin = min(tiles[tile_index].entry_offset, arrayLength(&tile_entries));
+        let end = min(begin + atomicLoad(&tiles[tile_index].splat_count), arrayLength(&tile_entries));
+        tile_entry_range = vec2<u32>(begin, end);
+    }
+    let entry_range = workgroupUniformLoad(&tile_entry_range);
+    let begin = entry_range.x;
+    let count = entry_range.y - entry_range.x;
+    // Bitonic sort in which every comparison is ascending, so that the entries beyond count act as infinitely far away padding
+    var padded_count = 1u;
+    while(padded_count < count) {
+        padded_count <<= 1u;
+    }
+    for(var block_size = 2u; block_size <= padded_count; block_size <<= 1u) {
+        for(var distance = block_size >> 1u; distance > 0u; distance >>= 1u) {
+            for(var pair_index = gl_LocalInvocationIndex; pair_index < padded_count >> 1u; pair_index += 256u) {
+                let block_start = (pair_index / distance) * distance * 2u;
+                let lower = block_start + pair_index % distance;
+                var upper = lower + distance;
+                if(distance == block_size >> 1u) {
+                    // Compares the first half of the block to the second half in reverse
+                    upper = block_start + block_size - 1u - pair_index % distance;
+                }
+                if(upper < count) {
+                    let a = tile_entries[begin + lower];
+                    let b = tile_entries[begin + upper];
+                    if(isEntryLess(b, a)) {
+                        tile_entries[begin + lower] = b;
+                        tile_entries[begin + upper] = a;
+                    }
+                }
+            }
+            storageBarrier();
+        }
+    }
+    let pixel = gl_WorkGroupID.xy * TILE_SIZE + gl_LocalInvocationID.xy;
+    if(any(pixel >= uniforms.image_size)) {
+        return;
+    }
+    let view_position = screenToClipSpace(vec2<f32>(pixel) + vec2<f32>(0.5)) * uniforms.view_size;
+    var color = vec3<f32>(0.0);
+    var transmittance = 1.0;
+    for(var entry_index = begin; entry_index < entry_range.y; entry_index += 1u) {
+        let splat = projected_splats[tile_entries[entry_index].value];
+        let texcoord = splat.inverse_transformation * (view_position - splat.translation);
+        if(any(abs(texcoord) > vec2<f32>(uniforms.ellipse_margin))) {
+            continue;
+        }
+        let alpha = splat.color.a * exp(-0.5 * dot(texcoord, texcoord));
+        if(alpha < 1.0/255.0) {
+            continue;
+        }
+        color += splat.color.rgb * alpha * transmittance;
+        transmittance *= 1.0 - alpha;
+        // Early termination, everything behind is hidden
+        if(transmittance < 1.0/255.0) {
+            break;
+        }
+    }
+    tile_pixels[pixel.y * uniforms.image_size.x + pixel.x] = vec2<u32>(pack2x16float(color.rg), pack2x16float(vec2<f32>(color.b, 1.0 - transmittance)));
+}
+
+struct TileBlitOutput {
+    @builtin(position) gl_Position: vec4<f32>,
+    @location(0) screen_position: vec2<f32>,
+}
+
+// A single triangle which covers the whole viewport
+@vertex
+fn tileBlitVertex(
+    @builtin(vertex_index) gl_VertexID: u32,
+) -> TileBlitOutput {
+    var stage_out: TileBlitOutput;
+    let corner = vec2<f32>(f32((gl_VertexID << 1u) & 2u), f32(gl_VertexID & 2u));
+    stage_out.gl_Position = vec4<f32>(corner.x * 2.0 - 1.0, 1.0 - corner.y * 2.0, 0.0, 1.0);
+    stage_out.screen_position = corner * vec2<f32>(uniforms.image_size);
+    return stage_out;
+}
+
+// Blends the composited tiles, which are premultiplied, over the frame
+@fragment
+fn tileBlitFragment(
+    stage_in: TileBlitOutput,
+) -> FragmentOutput {
+    var stage_out: FragmentOutput;
+    let pixel = min(vec2<u32>(stage_in.screen_position), uniforms.image_size - vec2<u32>(1u));
+    let halves = composited_pixels[pixel.y * uniforms.image_size.x + pixel.x];
+    stage_out.gl_Color = vec4<f32>(unpack2x16float(halves.x), unpack2x16float(halves.y));
+    return stage_out;
+}
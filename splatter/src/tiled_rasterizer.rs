@@ -0,0 +1,340 @@
+//! Tile-based compute rasterizer of [DepthSorting::GpuTiled](crate::renderer::DepthSorting::GpuTiled)
+
+use crate::renderer::Configuration;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+
+/// Width and height of a screen tile in pixels, `TILE_SIZE` in shaders.wgsl
+pub const TILE_SIZE: u32 = 16;
+
+/// How many tiles each splat overlaps on average before the per tile lists run out of room
+///
+/// Frames which need more grow the lists for the following frames.
+pub const TILE_ENTRIES_PER_SPLAT: usize = 8;
+
+/// Size of `ProjectedSplat` in shaders.wgsl
+const PROJECTED_SPLAT_SIZE: usize = 64;
+
+/// Size of `Tile` in shaders.wgsl
+const TILE_STRUCT_SIZE: usize = 16;
+
+/// Size of a composited pixel, four half floats
+const PIXEL_SIZE: usize = 8;
+
+/// Buffers and bind groups of a viewport size, shared by all frames of that size
+#[derive(Debug)]
+pub(crate) struct TiledTargets {
+    viewport_size: wgpu::Extent3d,
+    tile_count: [u32; 2],
+    /// One more tile than the viewport has, which receives the total number of tile entries
+    tile_buffer: wgpu::Buffer,
+    tile_entry_capacity: usize,
+    compute_bind_group: wgpu::BindGroup,
+    blit_bind_group: wgpu::BindGroup,
+}
+
+/// Progress of reading back the number of tile entries a frame needed, which can only be mapped once the frame was submitted
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum EntryTotalReadback {
+    Idle,
+    Copied,
+    Mapping,
+    Mapped,
+}
+
+/// Pipelines and buffers of [DepthSorting::GpuTiled](crate::renderer::DepthSorting::GpuTiled)
+pub(crate) struct TiledRasterizer {
+    project_pipeline: wgpu::ComputePipeline,
+    scan_pipeline: wgpu::ComputePipeline,
+    bin_pipeline: wgpu::ComputePipeline,
+    rasterize_pipeline: wgpu::ComputePipeline,
+    blit_pipeline: wgpu::RenderPipeline,
+    compute_bind_group_layout: wgpu::BindGroupLayout,
+    blit_bind_group_layout: wgpu::BindGroupLayout,
+    projected_splat_buffer: wgpu::Buffer,
+    /// Number of tile entries the targets are created with, grows when a frame needs more
+    tile_entry_capacity: AtomicUsize,
+    /// Limited by the maximum storage buffer binding size
+    max_tile_entry_capacity: usize,
+    entry_total_readback_buffer: wgpu::Buffer,
+    entry_total_readback: Arc<Mutex<EntryTotalReadback>>,
+    /// Recreated when the viewport size or the tile entry capacity changes
+    targets: Mutex<Option<Arc<TiledTargets>>>,
+}
+
+impl TiledRasterizer {
+    /// The compute passes bind the sorting bind group of the renderer as group 0 and the blit binds its splat bind group
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        config: &Configuration,
+        shader: &wgpu::ShaderModule,
+        sorting_bind_group_layout: &wgpu::BindGroupLayout,
+        splat_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let storage_binding = |binding: u32, visibility: wgpu::ShaderStages, read_only: bool| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let compute_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Tile Bind Group Layout"),
+            entries: &[
+                storage_binding(0, wgpu::ShaderStages::COMPUTE, false),
+                storage_binding(1, wgpu::ShaderStages::COMPUTE, false),
+                storage_binding(2, wgpu::ShaderStages::COMPUTE, false),
+                storage_binding(3, wgpu::ShaderStages::COMPUTE, false),
+            ],
+        });
+        // Binding 4 aliases the composited pixels, which the fragment shader may only read
+        let blit_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Tile Blit Bind Group Layout"),
+            entries: &[storage_binding(4, wgpu::ShaderStages::FRAGMENT, true)],
+        });
+
+        let compute_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Tile Pipeline Layout"),
+            bind_group_layouts: &[sorting_bind_group_layout, &compute_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let create_compute_pipeline = |label: &str, entry_point: &str| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&compute_pipeline_layout),
+                module: shader,
+                entry_point,
+            })
+        };
+        let project_pipeline = create_compute_pipeline("Tile Project Pipeline", "tileProject");
+        let scan_pipeline = create_compute_pipeline("Tile Scan Pipeline", "tileScan");
+        let bin_pipeline = create_compute_pipeline("Tile Bin Pipeline", "tileBin");
+        let rasterize_pipeline = create_compute_pipeline("Tile Rasterize Pipeline", "tileRasterize");
+
+        // Splats are not depth tested, but the pipeline has to match the depth attachment of the render pass
+        let blit_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Tile Blit Pipeline"),
+            layout: Some(&device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Tile Blit Pipeline Layout"),
+                bind_group_layouts: &[splat_bind_group_layout, &blit_bind_group_layout],
+                push_constant_ranges: &[],
+            })),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: "tileBlitVertex",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: "tileBlitFragment",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.surface_configuration.format,
+                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: config.depth_format.map(|format| wgpu::DepthStencilState {
+                format,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: config.sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        });
+
+        let projected_splat_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Projected Splat Buffer"),
+            size: (config.max_splat_count.max(1) * PROJECTED_SPLAT_SIZE) as u64,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let limits = device.limits();
+        let max_tile_entry_capacity = limits
+            .max_storage_buffer_binding_size
+            .min(limits.max_buffer_size.min(u32::MAX as u64) as u32) as usize
+            / std::mem::size_of::<(u32, u32)>();
+        let entry_total_readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Tile Entry Total Readback Buffer"),
+            size: std::mem::size_of::<u32>() as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            project_pipeline,
+            scan_pipeline,
+            bin_pipeline,
+            rasterize_pipeline,
+            blit_pipeline,
+            compute_bind_group_layout,
+            blit_bind_group_layout,
+            projected_splat_buffer,
+            tile_entry_capacity: AtomicUsize::new((config.max_splat_count.max(1) * TILE_ENTRIES_PER_SPLAT).min(max_tile_entry_capacity)),
+            max_tile_entry_capacity,
+            entry_total_readback_buffer,
+            entry_total_readback: Arc::new(Mutex::new(EntryTotalReadback::Idle)),
+            targets: Mutex::new(None),
+        }
+    }
+
+    /// Reads back how many tile entries the last rasterized frame needed and grows the capacity if they did not fit
+    ///
+    /// Returns whether the capacity grew. Unless `wait` is set, the number is only picked up once the GPU has delivered it.
+    pub(crate) fn grow_tile_entries(&self, device: &wgpu::Device, wait: bool) -> bool {
+        let mut readback = self.entry_total_readback.lock().unwrap();
+        if *readback == EntryTotalReadback::Copied {
+            *readback = EntryTotalReadback::Mapping;
+            drop(readback);
+            let entry_total_readback = self.entry_total_readback.clone();
+            self.entry_total_readback_buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+                *entry_total_readback.lock().unwrap() = if result.is_ok() {
+                    EntryTotalReadback::Mapped
+                } else {
+                    EntryTotalReadback::Idle
+                };
+            });
+            readback = self.entry_total_readback.lock().unwrap();
+        }
+        if wait && *readback == EntryTotalReadback::Mapping {
+            drop(readback);
+            device.poll(wgpu::Maintain::Wait);
+            readback = self.entry_total_readback.lock().unwrap();
+        }
+        if *readback != EntryTotalReadback::Mapped {
+            return false;
+        }
+        let entry_total = u32::from_ne_bytes(self.entry_total_readback_buffer.slice(..).get_mapped_range()[0..4].try_into().unwrap()) as usize;
+        self.entry_total_readback_buffer.unmap();
+        *readback = EntryTotalReadback::Idle;
+        let capacity = self.tile_entry_capacity.load(Ordering::Relaxed);
+        if entry_total <= capacity || capacity == self.max_tile_entry_capacity {
+            return false;
+        }
+        self.tile_entry_capacity
+            .store(entry_total.next_power_of_two().min(self.max_tile_entry_capacity), Ordering::Relaxed);
+        true
+    }
+
+    /// The targets for `viewport_size`, reusing those of the previous frame if it had the same size and enough tile entries
+    pub(crate) fn prepare(&self, device: &wgpu::Device, viewport_size: wgpu::Extent3d) -> Arc<TiledTargets> {
+        self.grow_tile_entries(device, false);
+        let tile_entry_capacity = self.tile_entry_capacity.load(Ordering::Relaxed);
+        let mut targets = self.targets.lock().unwrap();
+        if let Some(targets) = targets
+            .as_ref()
+            .filter(|targets| targets.viewport_size == viewport_size && targets.tile_entry_capacity == tile_entry_capacity)
+        {
+            return targets.clone();
+        }
+        let tile_count = [viewport_size.width.div_ceil(TILE_SIZE), viewport_size.height.div_ceil(TILE_SIZE)];
+        let tile_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Tile Buffer"),
+            size: (tile_count[0] * tile_count[1] + 1) as u64 * TILE_STRUCT_SIZE as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let tile_entry_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Tile Entry Buffer"),
+            size: (tile_entry_capacity * std::mem::size_of::<(u32, u32)>()) as u64,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let pixel_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Tile Pixel Buffer"),
+            size: (viewport_size.width * viewport_size.height) as u64 * PIXEL_SIZE as u64,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let compute_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Tile Bind Group"),
+            layout: &self.compute_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.projected_splat_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: tile_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: tile_entry_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: pixel_buffer.as_entire_binding(),
+                },
+            ],
+        });
+        let blit_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Tile Blit Bind Group"),
+            layout: &self.blit_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 4,
+                resource: pixel_buffer.as_entire_binding(),
+            }],
+        });
+        targets
+            .insert(Arc::new(TiledTargets {
+                viewport_size,
+                tile_count,
+                tile_buffer,
+                tile_entry_capacity,
+                compute_bind_group,
+                blit_bind_group,
+            }))
+            .clone()
+    }
+
+    /// Records the compute passes which bin, sort and composite the splats into the pixels of `targets`
+    pub(crate) fn rasterize(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        sorting_bind_group: &wgpu::BindGroup,
+        targets: &TiledTargets,
+        splat_count: usize,
+    ) {
+        encoder.clear_buffer(&targets.tile_buffer, 0, None);
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None });
+        compute_pass.set_bind_group(0, sorting_bind_group, &[]);
+        compute_pass.set_bind_group(1, &targets.compute_bind_group, &[]);
+        let workgroup_count = splat_count.div_ceil(256) as u32;
+        compute_pass.set_pipeline(&self.project_pipeline);
+        compute_pass.dispatch_workgroups(workgroup_count, 1, 1);
+        compute_pass.set_pipeline(&self.scan_pipeline);
+        compute_pass.dispatch_workgroups(1, 1, 1);
+        compute_pass.set_pipeline(&self.bin_pipeline);
+        compute_pass.dispatch_workgroups(workgroup_count, 1, 1);
+        compute_pass.set_pipeline(&self.rasterize_pipeline);
+        compute_pass.dispatch_workgroups(targets.tile_count[0], targets.tile_count[1], 1);
+        drop(compute_pass);
+        // The readback buffer can not be copied into while it is being mapped, that frame is not checked then
+        let mut readback = self.entry_total_readback.lock().unwrap();
+        if *readback == EntryTotalReadback::Idle {
+            // entry_offset of the extra tile
+            let entry_total_offset = (targets.tile_count[0] * targets.tile_count[1]) as u64 * TILE_STRUCT_SIZE as u64 + 4;
+            encoder.copy_buffer_to_buffer(&targets.tile_buffer, entry_total_offset, &self.entry_total_readback_buffer, 0, 4);
+            *readback = EntryTotalReadback::Copied;
+        }
+    }
+
+    /// Blends the composited pixels over `render_pass`, which has to bind the splat bind group of the renderer as group 0
+    pub(crate) fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, targets: &'a TiledTargets) {
+        render_pass.set_pipeline(&self.blit_pipeline);
+        render_pass.set_bind_group(1, &targets.blit_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
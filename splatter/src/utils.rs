@@ -64,6 +64,19 @@ pub fn mat4_multiplication(a: &[ppga3d::Point; 4], b: &[ppga3d::Point; 4]) -> [p
     ]
 }
 
+/// Inverts a 4x4 matrix whose upper 3x3 part is orthonormal, such as the ones of [motor3d_to_mat4]
+pub fn mat4_orthonormal_inverse(a: &[ppga3d::Point; 4]) -> [ppga3d::Point; 4] {
+    let mut result = [ppga3d::Point::zero(); 4];
+    for column in 0..3 {
+        for row in 0..3 {
+            result[column][row] = a[row][column];
+        }
+        result[3][column] = -(a[column][0] * a[3][0] + a[column][1] * a[3][1] + a[column][2] * a[3][2]);
+    }
+    result[3][3] = 1.0;
+    result
+}
+
 /// Calculates the product a 4x4 matrix and a point
 pub fn mat4_transform(a: &[ppga3d::Point; 4], b: &ppga3d::Point) -> ppga3d::Point {
     a[0] * b[0] + a[1] * b[1] + a[2] * b[2] + a[3] * b[3]
@@ -27,12 +27,14 @@ impl Default for Weapon {
     }
 }
 
+#[allow(dead_code)] // Not simulated by the demo yet
 #[derive(Component)]
 pub struct Bullet {
     pub speed: f32,
     pub damage: f32,
 }
 
+#[allow(dead_code)] // Not simulated by the demo yet
 #[derive(Component)]
 pub struct ReloadTimer {
     pub weapon: Entity,
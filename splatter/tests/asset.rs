@@ -0,0 +1,154 @@
+use bevy::{
+    app::PluginsState,
+    asset::LoadState,
+    prelude::*,
+    render::{
+        camera::RenderTarget,
+        pipelined_rendering::PipelinedRenderingPlugin,
+        render_asset::RenderAssets,
+        render_resource::{Extent3d, TextureDimension, TextureFormat, TextureUsages},
+        RenderApp,
+    },
+    window::ExitCondition,
+    winit::WinitPlugin,
+};
+use splatter::{
+    asset::{GaussianCloud, GaussianCloudPlugin},
+    component::{GaussianSplat, GaussianSplatBundle},
+    render_plugin::{GaussianSplatConfiguration, GaussianSplatRenderPlugin, GaussianSplatViews},
+    scene::{GpuSplat, Scene},
+};
+use std::time::{Duration, Instant};
+
+fn write_ply(path: &std::path::Path, alpha: f32) {
+    let mut scene = Scene::new();
+    scene.set_splats(vec![GpuSplat {
+        alpha,
+        scale: [1.0; 3],
+        ..GpuSplat::default()
+    }]);
+    let mut data = Vec::new();
+    scene.save_ply(&mut data).unwrap();
+    std::fs::write(path, data).unwrap();
+}
+
+/// Updates `app` until `condition` holds, panics after a minute
+fn update_until(app: &mut App, mut condition: impl FnMut(&mut App) -> bool) {
+    let start = Instant::now();
+    while !condition(app) {
+        assert!(start.elapsed() < Duration::from_secs(60), "timed out");
+        app.update();
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// Directory of a test with a splat file in it
+fn test_directory(name: &str) -> std::path::PathBuf {
+    let directory = std::env::temp_dir().join(format!("splatter-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    write_ply(&directory.join("cloud.ply"), 0.25);
+    directory
+}
+
+fn asset_plugin(directory: &std::path::Path) -> AssetPlugin {
+    AssetPlugin {
+        file_path: directory.to_string_lossy().into_owned(),
+        watch_for_changes_override: Some(true),
+        ..default()
+    }
+}
+
+#[test]
+fn reloads_changed_files() {
+    let directory = test_directory("hot-reload");
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, asset_plugin(&directory), GaussianCloudPlugin));
+    let handle: Handle<GaussianCloud> = app.world.resource::<AssetServer>().load("cloud.ply");
+    update_until(&mut app, |app| {
+        app.world.resource::<AssetServer>().load_state(&handle) == LoadState::Loaded
+    });
+    let alpha = |app: &App| app.world.resource::<Assets<GaussianCloud>>().get(&handle).unwrap().splats[0].alpha;
+    assert!((alpha(&app) - 0.25).abs() < 1.0e-5);
+
+    // The watcher debounces events, so the change needs some time to arrive
+    write_ply(&directory.join("cloud.ply"), 0.75);
+    update_until(&mut app, |app| (alpha(app) - 0.75).abs() < 1.0e-5);
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn render_world_gathers_reloaded_clouds() {
+    let directory = test_directory("render-reload");
+    let mut app = App::new();
+    app.add_plugins((
+        DefaultPlugins
+            .set(WindowPlugin {
+                primary_window: None,
+                exit_condition: ExitCondition::DontExit,
+                close_when_requested: false,
+            })
+            .set(asset_plugin(&directory))
+            .disable::<WinitPlugin>()
+            // Keeps the render world in the app, where the test can look into it
+            .disable::<PipelinedRenderingPlugin>(),
+        GaussianCloudPlugin,
+        GaussianSplatRenderPlugin,
+    ));
+    // What App::run does before the first update
+    while app.plugins_state() != PluginsState::Ready {
+        bevy::tasks::tick_global_task_pools_on_main_thread();
+    }
+    app.finish();
+    app.cleanup();
+    if app.get_sub_app(RenderApp).is_err() {
+        eprintln!("no wgpu adapter available, skipping");
+        return;
+    }
+
+    let mut target = Image::new_fill(
+        Extent3d {
+            width: 64,
+            height: 64,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0; 4],
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    target.texture_descriptor.usage |= TextureUsages::RENDER_ATTACHMENT;
+    let target = app.world.resource_mut::<Assets<Image>>().add(target);
+    app.world.spawn(Camera3dBundle {
+        camera: Camera {
+            target: RenderTarget::Image(target),
+            ..default()
+        },
+        ..default()
+    });
+    let handle: Handle<GaussianCloud> = app.world.resource::<AssetServer>().load("cloud.ply");
+    app.world.spawn(GaussianSplatBundle {
+        splat: GaussianSplat { cloud: handle.clone() },
+        transform: Transform::from_xyz(0.0, 0.0, -5.0),
+        ..default()
+    });
+
+    // The uploaded splats of the cloud, and whether the renderer of the camera gathered them
+    let gathered = |app: &App| {
+        let render_world = &app.get_sub_app(RenderApp).unwrap().world;
+        let scene = render_world.resource::<RenderAssets<GaussianCloud>>().get(&handle)?;
+        let gathered_splats = render_world.resource::<GaussianSplatViews>().gathered_splats.as_ref()?;
+        gathered_splats.contains(scene).then(|| scene.splat_data[0].alpha)
+    };
+    update_until(&mut app, |app| gathered(app).is_some_and(|alpha| (alpha - 0.25).abs() < 1.0e-5));
+    write_ply(&directory.join("cloud.ply"), 0.75);
+    update_until(&mut app, |app| gathered(app).is_some_and(|alpha| (alpha - 0.75).abs() < 1.0e-5));
+
+    // An invalid configuration is rejected and the camera keeps rendering with its previous renderer
+    app.world.resource_mut::<GaussianSplatConfiguration>().radix_bits_per_digit = 3;
+    app.update();
+    app.update();
+    let render_world = &app.get_sub_app(RenderApp).unwrap().world;
+    let views = &render_world.resource::<GaussianSplatViews>().views;
+    assert_eq!(views.len(), 1);
+    assert!(views.values().all(|view_splats| view_splats.renderer.config().radix_bits_per_digit == 8));
+    std::fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,172 @@
+//! Fixtures shared by the test files, each of which only uses some of them
+#![allow(dead_code)]
+
+use geometric_algebra::{ppga3d::Motor, One};
+use splatter::{
+    renderer::{Camera, Configuration, DepthSorting, ModelTransform, Renderer},
+    scene::{GpuSplat, Scene},
+};
+
+/// Any adapter will do, including software ones. Returns [None] on machines without one, so that the tests can be skipped there
+pub fn device() -> Option<(wgpu::Device, wgpu::Queue)> {
+    let instance = wgpu::Instance::default();
+    let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))?;
+    // The radix sort needs more invocations per workgroup than the default limits allow
+    let descriptor = wgpu::DeviceDescriptor {
+        limits: adapter.limits(),
+        ..wgpu::DeviceDescriptor::default()
+    };
+    pollster::block_on(adapter.request_device(&descriptor, None)).ok()
+}
+
+/// Renders into [wgpu::TextureFormat::Rgba8Unorm], which can be compared to the [splatter::cpu_renderer::CpuRenderer] directly
+pub fn config(depth_sorting: DepthSorting) -> Configuration {
+    let mut config = Configuration {
+        depth_sorting,
+        max_splat_count: 1024,
+        ..Configuration::default()
+    };
+    config.surface_configuration.format = wgpu::TextureFormat::Rgba8Unorm;
+    config
+}
+
+/// A splat of a single view independent color, `rotation` does not have to be normalized
+pub fn splat(center: [f32; 3], scale: [f32; 3], rotation: [f32; 4], color: [f32; 3], alpha: f32) -> GpuSplat {
+    let norm = rotation.iter().map(|value| value * value).sum::<f32>().sqrt();
+    let mut color_sh = [0.0; 48];
+    for channel in 0..3 {
+        color_sh[channel] = (color[channel] - 0.5) / 0.282_094_8;
+    }
+    GpuSplat {
+        rotation: rotation.map(|value| value / norm),
+        center,
+        scale,
+        alpha,
+        color_sh,
+        ..GpuSplat::default()
+    }
+}
+
+/// A few overlapping splats of different shapes in front of the camera, one of them with view dependent color
+pub fn test_splats() -> Vec<GpuSplat> {
+    let mut splats = vec![
+        splat([0.0, 0.0, 6.0], [1.2, 0.6, 0.3], [0.9, 0.0, 0.3, 0.2], [0.9, 0.2, 0.1], 0.9),
+        splat([-1.5, 0.8, 8.0], [0.4, 1.5, 0.4], [0.8, 0.5, 0.0, 0.3], [0.1, 0.8, 0.3], 0.8),
+        splat([1.6, -0.5, 4.5], [0.5, 0.5, 0.5], [1.0, 0.0, 0.0, 0.0], [0.2, 0.3, 0.9], 0.6),
+        splat([0.5, 1.0, 5.0], [0.8, 0.2, 0.2], [0.7, 0.1, -0.6, 0.3], [0.9, 0.9, 0.2], 1.0),
+    ];
+    for (index, coefficient) in splats[3].color_sh[3..].iter_mut().enumerate() {
+        *coefficient = ((index * 5) % 7) as f32 / 7.0 - 0.5;
+    }
+    splats
+}
+
+/// [test_splats] without a [Scene::splat_buffer], for the [splatter::cpu_renderer::CpuRenderer]
+pub fn test_scene() -> Scene {
+    let mut scene = Scene::new();
+    scene.set_splats(test_splats());
+    scene
+}
+
+/// A scene of `splats` which is uploaded to the GPU
+pub fn scene_of(device: &wgpu::Device, splats: Vec<GpuSplat>) -> Scene {
+    let mut scene = Scene::new();
+    scene.set_splats(splats);
+    scene.create_splat_buffer(device);
+    scene
+}
+
+/// Camera at the origin looking along the positive Z axis
+pub fn camera(width: u32, height: u32) -> Camera {
+    Camera::from_motor(
+        Motor::one(),
+        wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+    )
+}
+
+/// Sum of the differences of all channels of all pixels
+pub fn total_difference(image: &[u8], reference: &[u8]) -> u64 {
+    image
+        .iter()
+        .zip(reference)
+        .map(|(pixel, reference)| pixel.abs_diff(*reference) as u64)
+        .sum()
+}
+
+/// Renders with [Renderer::render_frame] into a 64 x 64 texture whose depth buffer is cleared to `cleared_depth` beforehand
+pub fn render_frame_with_depth(device: &wgpu::Device, queue: &wgpu::Queue, renderer: &Renderer, scene: &Scene, cleared_depth: f32) -> Vec<u8> {
+    let size = wgpu::Extent3d {
+        width: 64,
+        height: 64,
+        depth_or_array_layers: 1,
+    };
+    let create_texture = |format: wgpu::TextureFormat, usage: wgpu::TextureUsages| {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | usage,
+            view_formats: &[],
+        })
+    };
+    let target = create_texture(wgpu::TextureFormat::Rgba8Unorm, wgpu::TextureUsages::COPY_SRC);
+    let target_view = target.create_view(&wgpu::TextureViewDescriptor::default());
+    let depth_view =
+        create_texture(wgpu::TextureFormat::Depth32Float, wgpu::TextureUsages::empty()).create_view(&wgpu::TextureViewDescriptor::default());
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: None,
+        color_attachments: &[],
+        depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+            view: &depth_view,
+            depth_ops: Some(wgpu::Operations {
+                load: wgpu::LoadOp::Clear(cleared_depth),
+                store: true,
+            }),
+            stencil_ops: None,
+        }),
+    });
+    queue.submit(Some(encoder.finish()));
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    renderer.render_frame(
+        device,
+        queue,
+        &target_view,
+        Some(&depth_view),
+        size,
+        Motor::one(),
+        &[(scene, ModelTransform::default())],
+    );
+    assert!(pollster::block_on(device.pop_error_scope()).is_none());
+    let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: 64 * 64 * 4,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+    encoder.copy_texture_to_buffer(
+        target.as_image_copy(),
+        wgpu::ImageCopyBuffer {
+            buffer: &readback_buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(64 * 4),
+                rows_per_image: Some(64),
+            },
+        },
+        size,
+    );
+    queue.submit(Some(encoder.finish()));
+    readback_buffer.slice(..).map_async(wgpu::MapMode::Read, |result| result.unwrap());
+    device.poll(wgpu::Maintain::Wait);
+    let data = readback_buffer.slice(..).get_mapped_range().to_vec();
+    data
+}
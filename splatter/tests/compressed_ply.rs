@@ -0,0 +1,202 @@
+use splatter::scene::{PlyError, PlyHeader, Scene};
+use std::io::Cursor;
+
+const SPLAT_COUNT: usize = 300;
+const CHUNK_COUNT: usize = 2;
+
+fn quantize(value: f32, bits: u32) -> u32 {
+    (value.clamp(0.0, 1.0) * ((1 << bits) - 1) as f32).round() as u32
+}
+
+fn pack_111011(value: [f32; 3]) -> u32 {
+    quantize(value[0], 11) << 21 | quantize(value[1], 10) << 11 | quantize(value[2], 11)
+}
+
+fn pack_rotation(rotation: [f32; 4]) -> u32 {
+    let largest = (0..4).max_by(|a, b| rotation[*a].abs().total_cmp(&rotation[*b].abs())).unwrap();
+    let sign = rotation[largest].signum();
+    let mut packed = largest as u32;
+    for (index, component) in rotation.iter().enumerate() {
+        if index != largest {
+            packed = packed << 10 | quantize(component * sign * std::f32::consts::FRAC_1_SQRT_2 + 0.5, 10);
+        }
+    }
+    packed
+}
+
+/// Position, rotation, log scale, color, opacity and first rest coefficient of the splat at `index`
+fn expected_splat(index: usize) -> ([f32; 3], [f32; 4], [f32; 3], [f32; 3], f32, f32) {
+    let t = index as f32 / SPLAT_COUNT as f32;
+    let rotation = [0.8, 0.2 - t * 0.4, 0.4, -0.3];
+    let norm = rotation.iter().map(|value: &f32| value * value).sum::<f32>().sqrt();
+    (
+        [t * 4.0 - 2.0, 1.0 - t, t * t],
+        rotation.map(|value| value / norm),
+        [-3.0 + t, -2.0, -1.0 - t],
+        [t, 0.5, 1.0 - t],
+        0.25 + t * 0.5,
+        t - 0.5,
+    )
+}
+
+/// Builds a compressed PLY with per chunk color bounds and 9 rest coefficients
+fn compressed_ply() -> Vec<u8> {
+    let chunk_properties = [
+        "min_x",
+        "min_y",
+        "min_z",
+        "max_x",
+        "max_y",
+        "max_z",
+        "min_scale_x",
+        "min_scale_y",
+        "min_scale_z",
+        "max_scale_x",
+        "max_scale_y",
+        "max_scale_z",
+        "min_r",
+        "min_g",
+        "min_b",
+        "max_r",
+        "max_g",
+        "max_b",
+    ];
+    let mut data = format!("ply\nformat binary_little_endian 1.0\nelement chunk {}\n", CHUNK_COUNT);
+    for name in chunk_properties {
+        data += &format!("property float {}\n", name);
+    }
+    data += &format!("element vertex {}\n", SPLAT_COUNT);
+    for name in ["packed_position", "packed_rotation", "packed_scale", "packed_color"] {
+        data += &format!("property uint {}\n", name);
+    }
+    data += &format!("element sh {}\n", SPLAT_COUNT);
+    for index in 0..9 {
+        data += &format!("property uchar f_rest_{}\n", index);
+    }
+    data += "end_header\n";
+    let mut data = data.into_bytes();
+    let bounds = |range: std::ops::Range<usize>, select: fn(usize) -> [f32; 3]| {
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        for index in range {
+            let value = select(index);
+            for axis in 0..3 {
+                min[axis] = min[axis].min(value[axis]);
+                max[axis] = max[axis].max(value[axis]);
+            }
+        }
+        (min, max)
+    };
+    let chunks = (0..CHUNK_COUNT)
+        .map(|chunk| {
+            let range = chunk * 256..((chunk + 1) * 256).min(SPLAT_COUNT);
+            (
+                bounds(range.clone(), |index| expected_splat(index).0),
+                bounds(range.clone(), |index| expected_splat(index).2),
+                bounds(range, |index| expected_splat(index).3),
+            )
+        })
+        .collect::<Vec<_>>();
+    for ((min_position, max_position), (min_scale, max_scale), (min_color, max_color)) in &chunks {
+        for value in [min_position, max_position, min_scale, max_scale, min_color, max_color]
+            .into_iter()
+            .flatten()
+        {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    let normalize = |value: [f32; 3], (min, max): &([f32; 3], [f32; 3])| {
+        [0, 1, 2].map(|axis| {
+            if max[axis] > min[axis] {
+                (value[axis] - min[axis]) / (max[axis] - min[axis])
+            } else {
+                0.0
+            }
+        })
+    };
+    for index in 0..SPLAT_COUNT {
+        let (position, rotation, scale, color, opacity, _) = expected_splat(index);
+        let (position_bounds, scale_bounds, color_bounds) = &chunks[index / 256];
+        let color = normalize(color, color_bounds);
+        let packed_color = quantize(color[0], 8) << 24 | quantize(color[1], 8) << 16 | quantize(color[2], 8) << 8 | quantize(opacity, 8);
+        for packed in [
+            pack_111011(normalize(position, position_bounds)),
+            pack_rotation(rotation),
+            pack_111011(normalize(scale, scale_bounds)),
+            packed_color,
+        ] {
+            data.extend_from_slice(&packed.to_le_bytes());
+        }
+    }
+    for index in 0..SPLAT_COUNT {
+        let rest = expected_splat(index).5;
+        for _ in 0..9 {
+            data.push(((rest / 8.0 + 0.5) * 256.0).clamp(0.0, 255.0) as u8);
+        }
+    }
+    data
+}
+
+#[test]
+fn decode_compressed() {
+    let data = compressed_ply();
+    let header = PlyHeader::parse(&mut Cursor::new(&data)).unwrap();
+    assert!(header.is_compressed());
+    header.validate().unwrap();
+    assert_eq!(header.splat_count(), SPLAT_COUNT);
+
+    let mut scene = Scene::new();
+    scene.load_ply(&mut Cursor::new(&data)).unwrap();
+    assert_eq!(scene.splat_count, SPLAT_COUNT);
+    for (index, splat) in scene.splat_data.iter().enumerate() {
+        let (position, rotation, scale, color, opacity, rest) = expected_splat(index);
+        for axis in 0..3 {
+            assert!((splat.center[axis] - position[axis]).abs() < 4.0 / 1023.0);
+            assert!((splat.scale[axis].ln() - scale[axis]).abs() < 1.0 / 1023.0);
+            assert!((0.5 + 0.282_094_8 * splat.color_sh[axis] - color[axis]).abs() < 1.0 / 255.0);
+        }
+        let dot = splat.rotation.iter().zip(rotation.iter()).map(|(a, b)| a * b).sum::<f32>();
+        assert!(dot.abs() > 0.9999);
+        assert!((splat.alpha - opacity).abs() < 1.0 / 255.0);
+        assert!(splat.color_sh[3..12].iter().all(|coefficient| (coefficient - rest).abs() < 8.0 / 256.0));
+        assert!(splat.color_sh[12..].iter().all(|coefficient| *coefficient == 0.0));
+    }
+}
+
+#[test]
+fn reject_insufficient_chunks() {
+    let data = compressed_ply();
+    let text = String::from_utf8_lossy(&data[0..64]).replace("element chunk 2", "element chunk 1");
+    let mut data = data;
+    data[0..64].copy_from_slice(&text.as_bytes()[0..64]);
+    let header = PlyHeader::parse(&mut Cursor::new(&data)).unwrap();
+    assert!(matches!(header.validate(), Err(PlyError::InsufficientChunks { chunks: 1, splats: 300 })));
+}
+
+#[test]
+fn reject_ascii() {
+    let data = String::from_utf8_lossy(&compressed_ply()).replace("binary_little_endian", "ascii");
+    let header = PlyHeader::parse(&mut Cursor::new(data.as_bytes())).unwrap();
+    assert!(matches!(header.validate(), Err(PlyError::CompressedAscii)));
+}
+
+#[test]
+fn reject_truncated_body() {
+    let mut data = compressed_ply();
+    data.truncate(data.len() - 1);
+    assert!(matches!(
+        Scene::new().load_ply(&mut Cursor::new(&data)),
+        Err(PlyError::UnexpectedEndOfBody)
+    ));
+    // Must fail without trying to allocate memory for the declared splats
+    let data = compressed_ply();
+    let header_size = data.windows(11).position(|window| window == b"end_header\n").unwrap() + 11;
+    let header = String::from_utf8_lossy(&data[0..header_size])
+        .replace("element chunk 2", "element chunk 20000000")
+        .replace("element vertex 300", "element vertex 4000000000")
+        .replace("element sh 300", "element sh 4000000000");
+    assert!(matches!(
+        Scene::new().load_ply(&mut Cursor::new(header.as_bytes())),
+        Err(PlyError::UnexpectedEndOfBody)
+    ));
+}
@@ -0,0 +1,157 @@
+mod common;
+
+use common::{camera, splat, test_scene};
+use geometric_algebra::{
+    ppga3d::{Rotor, Translator},
+    GeometricProduct,
+};
+use splatter::{
+    cpu_renderer::CpuRenderer,
+    renderer::{Configuration, ModelTransform},
+    scene::{GpuSplat, Scene},
+};
+
+const WIDTH: u32 = 64;
+const HEIGHT: u32 = 48;
+
+fn render(config: Configuration, scene: &Scene) -> Vec<u8> {
+    CpuRenderer::new(config).render(&camera(WIDTH, HEIGHT), WIDTH, HEIGHT, &[(scene, ModelTransform::default())])
+}
+
+/// Compares against `tests/golden/<name>.png`, set `UPDATE_GOLDEN=1` to rewrite the image instead
+fn assert_golden(name: &str, pixels: &[u8]) {
+    let path = format!("{}/tests/golden/{}.png", env!("CARGO_MANIFEST_DIR"), name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        image::save_buffer(&path, pixels, WIDTH, HEIGHT, image::ColorType::Rgba8).unwrap();
+        return;
+    }
+    let golden = image::open(&path).unwrap().into_rgba8();
+    assert_eq!(golden.dimensions(), (WIDTH, HEIGHT));
+    // Leaves room for differences in floating point rounding between platforms
+    let max_difference = golden
+        .as_raw()
+        .iter()
+        .zip(pixels)
+        .map(|(golden, pixel)| golden.abs_diff(*pixel))
+        .max()
+        .unwrap();
+    assert!(max_difference <= 2, "{} differs by up to {}", name, max_difference);
+}
+
+#[test]
+fn golden_default() {
+    let pixels = render(Configuration::default(), &test_scene());
+    assert!(pixels.chunks(4).any(|pixel| pixel[3] > 200));
+    assert_golden("default", &pixels);
+}
+
+#[test]
+fn golden_contour_aligned() {
+    let config = Configuration {
+        use_covariance_for_scale: false,
+        use_unaligned_rectangles: false,
+        ..Configuration::default()
+    };
+    assert_golden("contour_aligned", &render(config, &test_scene()));
+}
+
+#[test]
+fn golden_without_spherical_harmonics() {
+    let config = Configuration {
+        spherical_harmonics_order: 0,
+        ..Configuration::default()
+    };
+    assert_golden("without_spherical_harmonics", &render(config, &test_scene()));
+}
+
+#[test]
+fn empty_scene_is_transparent() {
+    assert!(render(Configuration::default(), &Scene::new()).iter().all(|value| *value == 0));
+}
+
+#[test]
+fn culls_splats_behind_the_camera() {
+    let mut scene = Scene::new();
+    scene.set_splats(vec![splat([0.0, 0.0, 5.0], [1.0, 0.8, 1.0], [0.9, 0.0, 0.0, 0.3], [1.0; 3], 1.0)]);
+    assert!(render(Configuration::default(), &scene).iter().any(|value| *value != 0));
+    scene.set_splats(vec![splat([0.0, 0.0, -5.0], [1.0, 0.8, 1.0], [0.9, 0.0, 0.0, 0.3], [1.0; 3], 1.0)]);
+    assert!(render(Configuration::default(), &scene).iter().all(|value| *value == 0));
+}
+
+#[test]
+fn blends_back_to_front() {
+    // Ellipses aligned to the view axes are degenerate for the decomposition, like on the GPU, so they are rotated a bit
+    let front = splat([0.0, 0.0, 3.0], [0.5, 0.4, 0.5], [0.9, 0.0, 0.0, 0.3], [1.0, 0.0, 0.0], 1.0);
+    let back = splat([0.0, 0.0, 6.0], [1.0, 0.8, 1.0], [0.9, 0.0, 0.0, 0.3], [0.0, 0.0, 1.0], 1.0);
+    let center = ((HEIGHT / 2 * WIDTH + WIDTH / 2) * 4) as usize;
+    let images = [vec![front, back], vec![back, front]].map(|splats| {
+        let mut scene = Scene::new();
+        scene.set_splats(splats);
+        render(Configuration::default(), &scene)
+    });
+    assert_eq!(images[0], images[1]);
+    // The almost opaque center of the front splat hides most of the back splat
+    let pixel = &images[0][center..center + 4];
+    assert!(pixel[0] > 240 && pixel[1] == 0 && pixel[2] < 10 && pixel[3] == 255);
+}
+
+/// Hamilton product of two quaternions with the real part first
+fn quaternion_product(a: [f32; 4], b: [f32; 4]) -> [f32; 4] {
+    [
+        a[0] * b[0] - a[1] * b[1] - a[2] * b[2] - a[3] * b[3],
+        a[0] * b[1] + a[1] * b[0] + a[2] * b[3] - a[3] * b[2],
+        a[0] * b[2] - a[1] * b[3] + a[2] * b[0] + a[3] * b[1],
+        a[0] * b[3] + a[1] * b[2] - a[2] * b[1] + a[3] * b[0],
+    ]
+}
+
+#[test]
+fn model_transform_matches_transformed_splats() {
+    let rotation = [0.98, 0.05, -0.1, 0.12];
+    let rotation = rotation.map(|value: f32| value / rotation.iter().map(|value| value * value).sum::<f32>().sqrt());
+    let model_transform = ModelTransform {
+        motor: Translator::new(1.0, -0.5 * 0.4, -0.5 * 0.3, -0.5 * -1.5).geometric_product(Rotor::new(
+            rotation[0],
+            -rotation[1],
+            -rotation[2],
+            -rotation[3],
+        )),
+        scale: 1.2,
+        splat_scale: 0.8,
+    };
+    // Recovers the rotation of the model from its matrix, so that the test does not depend on the conventions of the motor
+    let matrix = model_transform.matrix();
+    let m = |row: usize, column: usize| matrix[column][row] / model_transform.scale;
+    let s = (m(0, 0) + m(1, 1) + m(2, 2) + 1.0).sqrt() * 2.0;
+    let model_rotation = [0.25 * s, (m(2, 1) - m(1, 2)) / s, (m(0, 2) - m(2, 0)) / s, (m(1, 0) - m(0, 1)) / s];
+
+    // Spherical harmonics would have to be rotated as well, so the splat with view dependent color is left out
+    let mut scene = test_scene();
+    scene.set_splats(scene.splat_data[0..3].to_vec());
+    let mut transformed = Scene::new();
+    transformed.set_splats(
+        scene
+            .splat_data
+            .iter()
+            .map(|splat| GpuSplat {
+                rotation: quaternion_product(model_rotation, splat.rotation),
+                center: [0, 1, 2].map(|row| (0..3).map(|column| matrix[column][row] * splat.center[column]).sum::<f32>() + matrix[3][row]),
+                scale: splat.scale.map(|scale| scale * model_transform.scale * model_transform.splat_scale),
+                ..*splat
+            })
+            .collect(),
+    );
+
+    let renderer = CpuRenderer::new(Configuration::default());
+    let placed = renderer.render(&camera(WIDTH, HEIGHT), WIDTH, HEIGHT, &[(&scene, model_transform)]);
+    let expected = renderer.render(&camera(WIDTH, HEIGHT), WIDTH, HEIGHT, &[(&transformed, ModelTransform::default())]);
+    assert!(expected.chunks(4).filter(|pixel| pixel[3] > 100).count() > 50);
+    assert_ne!(placed, render(Configuration::default(), &scene));
+    let max_difference = placed
+        .iter()
+        .zip(&expected)
+        .map(|(placed, expected)| placed.abs_diff(*expected))
+        .max()
+        .unwrap();
+    assert!(max_difference <= 2, "differs by up to {}", max_difference);
+}
@@ -0,0 +1,162 @@
+use geometric_algebra::ppga3d::Point;
+use splatter::cpu_sorter::{order_preserving_key, BackgroundSorter, CpuSorter, SortView, SortedEntries};
+
+/// Deterministic pseudo random depths in -100..100
+fn random_depths(count: usize, seed: u64) -> Vec<f32> {
+    let mut state = seed;
+    (0..count)
+        .map(|_| {
+            state = state.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+            ((state >> 40) as f32 / (1 << 24) as f32 - 0.5) * 200.0
+        })
+        .collect()
+}
+
+/// Checks that `order` contains all splats except the culled ones with NaN depths, back to front
+///
+/// Splats of equal depth may come in any order, as the sorter keeps them in the order of the previous frame.
+fn assert_back_to_front(order: &[u32], depths: &[f32]) {
+    assert!(order.windows(2).all(|pair| depths[pair[0] as usize] >= depths[pair[1] as usize]));
+    let mut splats = order.to_vec();
+    splats.sort_unstable();
+    let visible: Vec<u32> = (0..depths.len() as u32).filter(|index| !depths[*index as usize].is_nan()).collect();
+    assert_eq!(splats, visible);
+}
+
+fn sort(sorter: &mut CpuSorter, depths: &[f32]) -> Vec<u32> {
+    sorter
+        .sort(depths.len(), |index| Some(depths[index]).filter(|depth| !depth.is_nan()))
+        .iter()
+        .map(|entry| entry.1)
+        .collect()
+}
+
+#[test]
+fn keys_preserve_order() {
+    let values = [
+        f32::NEG_INFINITY,
+        -1e30,
+        -2.5,
+        -1.0,
+        -1e-30,
+        -0.0,
+        0.0,
+        1e-30,
+        1.0,
+        2.5,
+        1e30,
+        f32::INFINITY,
+    ];
+    for pair in values.windows(2) {
+        assert!(order_preserving_key(pair[0]) < order_preserving_key(pair[1]), "{} < {}", pair[0], pair[1]);
+    }
+}
+
+#[test]
+fn sorts_back_to_front() {
+    let mut depths = random_depths(5000, 1);
+    for index in (0..depths.len()).step_by(7) {
+        depths[index] = f32::NAN;
+    }
+    for thread_count in [1, 4] {
+        assert_back_to_front(&sort(&mut CpuSorter::new(thread_count), &depths), &depths);
+    }
+}
+
+#[test]
+fn multithreading_gives_the_same_result() {
+    // Enough entries to be split among threads, with many equal keys to check stability
+    let depths: Vec<f32> = random_depths(100_000, 2).iter().map(|depth| depth.round()).collect();
+    assert_eq!(sort(&mut CpuSorter::new(1), &depths), sort(&mut CpuSorter::new(8), &depths));
+}
+
+#[test]
+fn resorts_incrementally() {
+    let mut sorter = CpuSorter::new(1);
+    let mut depths = random_depths(10_000, 3);
+    sort(&mut sorter, &depths);
+    assert!(!sorter.was_incremental());
+    // A small camera motion only swaps a few neighbours
+    for depth in depths.iter_mut() {
+        *depth += 0.01 * depth.sin();
+    }
+    assert_back_to_front(&sort(&mut sorter, &depths), &depths);
+    assert!(sorter.was_incremental());
+    // A large one falls back to the radix sort
+    let depths = random_depths(10_000, 4);
+    assert_back_to_front(&sort(&mut sorter, &depths), &depths);
+    assert!(!sorter.was_incremental());
+}
+
+#[test]
+fn culled_splats_keep_coherence() {
+    let mut sorter = CpuSorter::new(1);
+    let mut depths = random_depths(1000, 5);
+    sort(&mut sorter, &depths);
+    let visible = depths.clone();
+    depths[10..20].fill(f32::NAN);
+    assert_back_to_front(&sort(&mut sorter, &depths), &depths);
+    assert_back_to_front(&sort(&mut sorter, &visible), &visible);
+    assert_eq!(sorter.entries().len(), visible.len());
+}
+
+#[test]
+fn adapts_to_the_splat_count() {
+    let mut sorter = CpuSorter::new(2);
+    for count in [100, 0, 3000, 50] {
+        let depths = random_depths(count, count as u64);
+        assert_back_to_front(&sort(&mut sorter, &depths), &depths);
+    }
+}
+
+/// Maps model space straight to clip space, with the depth in the range 0..1 taken from z
+fn identity_view(model_splat_offsets: Vec<usize>) -> SortView {
+    let identity = [
+        Point::new(1.0, 0.0, 0.0, 0.0),
+        Point::new(0.0, 1.0, 0.0, 0.0),
+        Point::new(0.0, 0.0, 1.0, 0.0),
+        Point::new(0.0, 0.0, 0.0, 1.0),
+    ];
+    SortView {
+        model_view_projection_matrices: vec![identity; model_splat_offsets.len()],
+        model_splat_offsets,
+        frustum_culling_tolerance: 1.0,
+    }
+}
+
+fn wait_for_result(sorter: &BackgroundSorter) -> SortedEntries {
+    for _ in 0..1000 {
+        if let Some(sorted) = sorter.latest() {
+            return sorted;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    panic!("background sorter did not publish a result");
+}
+
+#[test]
+fn sorts_in_the_background() {
+    let depths: Vec<f32> = random_depths(2000, 6).iter().map(|depth| depth / 200.0 + 0.5).collect();
+    let positions: Vec<f32> = depths.iter().flat_map(|depth| [0.0, 0.0, *depth]).collect();
+    let sorter = BackgroundSorter::new();
+    sorter.set_positions(positions);
+    sorter.request(identity_view(vec![0]), 2);
+    let sorted = wait_for_result(&sorter);
+    assert_eq!(sorted.splat_count, depths.len());
+    assert_back_to_front(&sorted.entries.iter().map(|entry| entry.1).collect::<Vec<_>>(), &depths);
+    assert!(sorter.latest().is_none());
+}
+
+#[test]
+fn background_sorter_picks_up_new_positions() {
+    let sorter = BackgroundSorter::new();
+    sorter.set_positions(vec![0.0, 0.0, 0.5]);
+    sorter.request(identity_view(vec![0]), 1);
+    assert_eq!(wait_for_result(&sorter).entries.len(), 1);
+    // The second model is culled for being outside of the frustum
+    sorter.set_positions(vec![0.0, 0.0, 0.2, 0.0, 0.0, 0.8, 5.0, 0.0, 0.5]);
+    sorter.request(identity_view(vec![0, 2]), 1);
+    let sorted = wait_for_result(&sorter);
+    assert_eq!(sorted.splat_count, 3);
+    assert_eq!(sorted.entries.iter().map(|entry| entry.1).collect::<Vec<_>>(), [1, 0]);
+}
@@ -0,0 +1,56 @@
+mod common;
+
+use common::{config, device, render_frame_with_depth, scene_of, test_splats};
+use geometric_algebra::{ppga3d::Motor, One};
+use splatter::renderer::{Configuration, DepthSorting, ModelTransform, Renderer};
+
+#[test]
+fn render_frame_tests_against_depth_view() {
+    let Some((device, queue)) = device() else {
+        eprintln!("no wgpu adapter available, skipping");
+        return;
+    };
+    let scene = scene_of(&device, test_splats());
+    let renderer = Renderer::new(
+        &device,
+        Configuration {
+            depth_format: Some(wgpu::TextureFormat::Depth32Float),
+            reversed_depth: true,
+            ..config(DepthSorting::Cpu)
+        },
+    );
+    let behind_everything = render_frame_with_depth(&device, &queue, &renderer, &scene, 0.0);
+    assert!(behind_everything.chunks(4).any(|pixel| pixel[0..3] != [0; 3]));
+    let in_front_of_everything = render_frame_with_depth(&device, &queue, &renderer, &scene, 1.0);
+    assert!(in_front_of_everything.chunks(4).all(|pixel| pixel[0..3] == [0; 3]));
+    // Without a depth view the pipelines would not match the render pass
+    let size = wgpu::Extent3d {
+        width: 64,
+        height: 64,
+        depth_or_array_layers: 1,
+    };
+    let target_view = device
+        .create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        })
+        .create_view(&wgpu::TextureViewDescriptor::default());
+    let without_depth_view = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        renderer.render_frame(
+            &device,
+            &queue,
+            &target_view,
+            None,
+            size,
+            Motor::one(),
+            &[(&scene, ModelTransform::default())],
+        );
+    }));
+    assert!(without_depth_view.is_err());
+}
@@ -0,0 +1,71 @@
+mod common;
+
+use common::{camera, config, device, scene_of, test_splats, total_difference};
+use splatter::{
+    cpu_renderer::CpuRenderer,
+    renderer::{DepthSorting, ModelTransform, Renderer},
+};
+
+/// Not a multiple of 64 pixels, so that the rows of the readback are padded
+const WIDTH: u32 = 67;
+const HEIGHT: u32 = 45;
+
+#[test]
+fn matches_cpu_renderer() {
+    let Some((device, queue)) = device() else {
+        eprintln!("no wgpu adapter available, skipping");
+        return;
+    };
+    let scene = scene_of(&device, test_splats());
+    for depth_sorting in [DepthSorting::Cpu, DepthSorting::Gpu] {
+        let config = config(depth_sorting);
+        let image = Renderer::new(&device, config.clone()).render_to_image(&device, &queue, &scene, &camera(WIDTH, HEIGHT), WIDTH, HEIGHT);
+        assert_eq!(image.dimensions(), (WIDTH, HEIGHT));
+        assert_eq!(image.get_pixel(0, 0).0, [0; 4]);
+        assert!(image.pixels().any(|pixel| pixel.0[3] > 200));
+        let reference = CpuRenderer::new(config).render(&camera(WIDTH, HEIGHT), WIDTH, HEIGHT, &[(&scene, ModelTransform::default())]);
+        let total_difference = total_difference(&image, &reference);
+        // Rasterization rules and float precision differ slightly between the two
+        assert!(
+            total_difference < reference.len() as u64,
+            "{:?} differs by {} in total",
+            depth_sorting,
+            total_difference
+        );
+    }
+}
+
+#[test]
+fn background_sorting_catches_up() {
+    let Some((device, queue)) = device() else {
+        eprintln!("no wgpu adapter available, skipping");
+        return;
+    };
+    let scene = scene_of(&device, test_splats());
+    let expected = Renderer::new(&device, config(DepthSorting::Cpu)).render_to_image(&device, &queue, &scene, &camera(WIDTH, HEIGHT), WIDTH, HEIGHT);
+    let renderer = Renderer::new(&device, config(DepthSorting::CpuBackground));
+    // The first frames are empty until the worker publishes its first result
+    for _ in 0..100 {
+        let image = renderer.render_to_image(&device, &queue, &scene, &camera(WIDTH, HEIGHT), WIDTH, HEIGHT);
+        if image.pixels().any(|pixel| pixel.0[3] != 0) {
+            assert_eq!(image, expected);
+            return;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    panic!("background sorting never finished");
+}
+
+#[test]
+fn swaps_bgra_into_rgba() {
+    let Some((device, queue)) = device() else {
+        eprintln!("no wgpu adapter available, skipping");
+        return;
+    };
+    let scene = scene_of(&device, test_splats());
+    let mut bgra_config = config(DepthSorting::Cpu);
+    bgra_config.surface_configuration.format = wgpu::TextureFormat::Bgra8Unorm;
+    let rgba = Renderer::new(&device, config(DepthSorting::Cpu)).render_to_image(&device, &queue, &scene, &camera(WIDTH, HEIGHT), WIDTH, HEIGHT);
+    let bgra = Renderer::new(&device, bgra_config).render_to_image(&device, &queue, &scene, &camera(WIDTH, HEIGHT), WIDTH, HEIGHT);
+    assert_eq!(rgba, bgra);
+}
@@ -0,0 +1,215 @@
+use splatter::scene::{
+    ply::{PlyFormat, PlyPropertyType, PlyScalarType},
+    GpuSplat, PlyError, PlyHeader, Scene,
+};
+use std::io::Cursor;
+
+const TEST_PLY: &str = include_str!("../test.ply");
+
+/// Re-encodes the ascii `test.ply` as a binary PLY with the given endianness
+fn to_binary(big_endian: bool) -> Vec<u8> {
+    let text = TEST_PLY.replace("\r\n", "\n");
+    let (header, body) = text.split_once("end_header\n").unwrap();
+    let format = if big_endian { "binary_big_endian" } else { "binary_little_endian" };
+    let mut data = header.replace("ascii", format).into_bytes();
+    data.extend_from_slice(b"end_header\n");
+    for line in body.lines().filter(|line| !line.trim().is_empty()) {
+        let values = line.split_whitespace().collect::<Vec<_>>();
+        for (index, value) in values.iter().enumerate() {
+            if index < 17 {
+                let value: f32 = value.parse().unwrap();
+                data.extend_from_slice(&if big_endian { value.to_be_bytes() } else { value.to_le_bytes() });
+            } else {
+                data.push(value.parse().unwrap());
+            }
+        }
+    }
+    data
+}
+
+fn check_test_scene(scene: &Scene) {
+    assert_eq!(scene.splat_count, 8);
+    assert_eq!(scene.splat_data.len(), 8);
+    assert_eq!(&scene.splat_positions[0..6], &[0.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+    assert_eq!(&scene.splat_positions[18..24], &[1.0, 1.0, 1.0, 0.0, 1.0, 1.0]);
+    for (index, splat) in scene.splat_data.iter().enumerate() {
+        assert_eq!(splat.center, scene.splat_positions[index * 3..index * 3 + 3]);
+        assert_eq!(splat.rotation, [1.0, 0.0, 0.0, 0.0]);
+        assert!(splat.scale.iter().all(|scale| (scale - std::f32::consts::E).abs() < 1.0e-5));
+        assert!((splat.alpha - 1.0 / (1.0 + (-1.0f32).exp())).abs() < 1.0e-6);
+        assert_eq!(&splat.color_sh[0..3], &[1.0, 1.0, 1.0]);
+        assert!(splat.color_sh[3..].iter().all(|coefficient| *coefficient == 0.0));
+    }
+}
+
+#[test]
+fn load_ascii() {
+    let mut scene = Scene::new();
+    scene.load_splat_file(concat!(env!("CARGO_MANIFEST_DIR"), "/test.ply")).unwrap();
+    check_test_scene(&scene);
+}
+
+#[test]
+fn load_binary_little_endian() {
+    let mut scene = Scene::new();
+    scene.load_ply(&mut Cursor::new(to_binary(false))).unwrap();
+    check_test_scene(&scene);
+}
+
+#[test]
+fn load_binary_big_endian() {
+    let mut scene = Scene::new();
+    scene.load_ply(&mut Cursor::new(to_binary(true))).unwrap();
+    check_test_scene(&scene);
+}
+
+#[test]
+fn header_schema() {
+    let header = PlyHeader::parse(&mut Cursor::new(TEST_PLY)).unwrap();
+    assert_eq!(header.format, PlyFormat::Ascii);
+    assert_eq!(header.splat_count(), 8);
+    let vertex = header.element("vertex").unwrap();
+    assert_eq!(vertex.properties.len(), 20);
+    assert_eq!(vertex.record_size(), Some(17 * 4 + 3));
+    assert_eq!(vertex.rest_coefficient_count(), 0);
+    let red = vertex.property("red").unwrap();
+    assert_eq!(red.property_type, PlyPropertyType::Scalar(PlyScalarType::UChar));
+    assert_eq!(red.offset, Some(17 * 4));
+    assert_eq!(vertex.property("rot_3").unwrap().offset, Some(16 * 4));
+    header.validate().unwrap();
+}
+
+#[test]
+fn reject_missing_and_mistyped_properties() {
+    let data = TEST_PLY
+        .replace("\r\n", "\n")
+        .replace("property float opacity\n", "")
+        .replace("property float scale_1\n", "")
+        .replace("property float rot_0\n", "property int rot_0\n");
+    let header = PlyHeader::parse(&mut Cursor::new(&data)).unwrap();
+    match header.validate() {
+        Err(PlyError::InvalidSchema { missing, mistyped }) => {
+            assert_eq!(missing, vec!["opacity".to_string(), "scale_1".to_string()]);
+            assert_eq!(mistyped, vec![("rot_0".to_string(), PlyPropertyType::Scalar(PlyScalarType::Int))]);
+        }
+        result => panic!("unexpected validation result {:?}", result),
+    }
+    assert!(matches!(
+        Scene::new().load_ply(&mut Cursor::new(&data)),
+        Err(PlyError::InvalidSchema { .. })
+    ));
+}
+
+#[test]
+fn reject_incomplete_spherical_harmonics() {
+    let data = TEST_PLY.replace("property float opacity\r\n", "property float f_rest_0\r\nproperty float opacity\r\n");
+    let header = PlyHeader::parse(&mut Cursor::new(data)).unwrap();
+    assert!(matches!(header.validate(), Err(PlyError::UnsupportedShCoefficientCount(1))));
+}
+
+#[test]
+fn parse_file_header() {
+    let file = std::fs::File::open(concat!(env!("CARGO_MANIFEST_DIR"), "/test.ply")).unwrap();
+    let (file_header_size, splat_count, _file) = Scene::parse_file_header(file).unwrap();
+    assert_eq!(file_header_size as usize, TEST_PLY.find("end_header").unwrap() + "end_header\r\n".len());
+    assert_eq!(splat_count, 8);
+}
+
+#[test]
+fn save_round_trip() {
+    let mut scene = Scene::new();
+    scene.load_splat_file(concat!(env!("CARGO_MANIFEST_DIR"), "/test.ply")).unwrap();
+    let mut splats = scene.splat_data.clone();
+    for (index, splat) in splats.iter_mut().enumerate() {
+        for (coefficient, value) in splat.color_sh[3..].iter_mut().enumerate() {
+            *value = (index * 45 + coefficient) as f32 * 0.01;
+        }
+    }
+    scene.set_splats(splats);
+    let mut data = Vec::new();
+    scene.save_ply(&mut data).unwrap();
+
+    let header = PlyHeader::parse(&mut Cursor::new(&data)).unwrap();
+    header.validate().unwrap();
+    assert_eq!(header.format, PlyFormat::BinaryLittleEndian);
+    let vertex = header.element("vertex").unwrap();
+    assert_eq!(vertex.rest_coefficient_count(), 45);
+    assert_eq!(vertex.record_size(), Some(62 * 4));
+
+    let mut loaded = Scene::new();
+    loaded.load_ply(&mut Cursor::new(&data)).unwrap();
+    assert_eq!(loaded.splat_positions, scene.splat_positions);
+    for (original, loaded) in scene.splat_data.iter().zip(loaded.splat_data.iter()) {
+        assert_eq!(loaded.center, original.center);
+        assert_eq!(loaded.rotation, original.rotation);
+        assert_eq!(loaded.color_sh, original.color_sh);
+        assert!((loaded.alpha - original.alpha).abs() < 1.0e-6);
+        for axis in 0..3 {
+            assert!((loaded.scale[axis] - original.scale[axis]).abs() < 1.0e-5);
+        }
+    }
+}
+
+#[test]
+fn save_round_trip_of_edge_values() {
+    // What Scene::allocate produces, as well as fully opaque and flat splats
+    let splats = vec![
+        GpuSplat::default(),
+        GpuSplat {
+            alpha: 1.0,
+            scale: [0.0, 1.0, 2.0],
+            ..GpuSplat::default()
+        },
+        GpuSplat {
+            alpha: 0.0,
+            scale: [-0.0, 0.5, 0.0],
+            ..GpuSplat::default()
+        },
+    ];
+    let mut scene = Scene::new();
+    scene.set_splats(splats.clone());
+    let mut data = Vec::new();
+    scene.save_ply(&mut data).unwrap();
+    let mut reader = Cursor::new(&data);
+    PlyHeader::parse(&mut reader).unwrap();
+    let body = &data[reader.position() as usize..];
+    assert!(body.chunks(4).all(|value| f32::from_le_bytes(value.try_into().unwrap()).is_finite()));
+
+    let mut loaded = Scene::new();
+    loaded.load_ply(&mut Cursor::new(&data)).unwrap();
+    for (original, loaded) in splats.iter().zip(loaded.splat_data.iter()) {
+        assert!((loaded.alpha - original.alpha).abs() < 1.0e-5);
+        for axis in 0..3 {
+            assert!(loaded.scale[axis].is_finite());
+            assert!((loaded.scale[axis] - original.scale[axis]).abs() < 1.0e-5);
+        }
+    }
+}
+
+#[test]
+fn reject_truncated_body() {
+    let mut data = to_binary(false);
+    data.truncate(data.len() - 10);
+    assert!(matches!(
+        Scene::new().load_ply(&mut Cursor::new(&data)),
+        Err(PlyError::UnexpectedEndOfBody)
+    ));
+    let text = TEST_PLY.replace("element vertex 8", "element vertex 9");
+    assert!(matches!(
+        Scene::new().load_ply(&mut Cursor::new(&text)),
+        Err(PlyError::UnexpectedEndOfBody)
+    ));
+}
+
+#[test]
+fn reject_huge_splat_count() {
+    // Must fail without trying to allocate memory for the declared splats
+    for data in [TEST_PLY.as_bytes().to_vec(), to_binary(true)] {
+        let header_size = data.windows(10).position(|window| window == b"end_header").unwrap();
+        let header = String::from_utf8_lossy(&data[0..header_size]).replace("element vertex 8", "element vertex 4000000000") + "end_header\n";
+        assert!(matches!(
+            Scene::new().load_ply(&mut Cursor::new(header)),
+            Err(PlyError::UnexpectedEndOfBody)
+        ));
+    }
+}
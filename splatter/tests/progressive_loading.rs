@@ -0,0 +1,70 @@
+mod common;
+
+use common::device;
+use splatter::{
+    scene::{PlyError, Scene},
+    utils::transmute_slice,
+};
+
+/// Copies [Scene::splat_buffer] back to the CPU
+fn read_splat_buffer(device: &wgpu::Device, queue: &wgpu::Queue, scene: &Scene) -> Vec<u8> {
+    let splat_buffer = scene.splat_buffer.as_ref().unwrap();
+    let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: splat_buffer.size(),
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+    encoder.copy_buffer_to_buffer(splat_buffer, 0, &readback_buffer, 0, splat_buffer.size());
+    queue.submit(Some(encoder.finish()));
+    readback_buffer.slice(..).map_async(wgpu::MapMode::Read, |result| result.unwrap());
+    device.poll(wgpu::Maintain::Wait);
+    let data = readback_buffer.slice(..).get_mapped_range().to_vec();
+    data
+}
+
+#[test]
+fn progressive_loading_matches_load_ply() {
+    let Some((device, queue)) = device() else {
+        eprintln!("no wgpu adapter available, skipping");
+        return;
+    };
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/test.ply");
+    let mut expected = Scene::new();
+    expected.load_splat_file(path).unwrap();
+
+    let (file_header_size, splat_count, mut file) = Scene::parse_file_header(std::fs::File::open(path).unwrap()).unwrap();
+    let mut scene = Scene::new();
+    scene.allocate(&device, splat_count);
+    for range in [5..8, 0..2, 2..5] {
+        scene.load_chunk(&queue, &mut file, file_header_size, range).unwrap();
+    }
+    assert_eq!(scene.splat_count, expected.splat_count);
+    assert_eq!(scene.splat_data, expected.splat_data);
+    assert_eq!(scene.splat_positions, expected.splat_positions);
+    assert_eq!(read_splat_buffer(&device, &queue, &scene), transmute_slice::<_, u8>(&expected.splat_data));
+}
+
+#[test]
+fn chunks_out_of_bounds_leave_the_scene_untouched() {
+    let Some((device, queue)) = device() else {
+        eprintln!("no wgpu adapter available, skipping");
+        return;
+    };
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/test.ply");
+    let (file_header_size, _, mut file) = Scene::parse_file_header(std::fs::File::open(path).unwrap()).unwrap();
+    // The GPU buffer only has room for half of the file
+    let mut scene = Scene::new();
+    scene.allocate(&device, 4);
+    scene.load_chunk(&queue, &mut file, file_header_size, 0..4).unwrap();
+    let revision = scene.revision();
+    let splat_data = scene.splat_data.clone();
+    assert!(matches!(
+        scene.load_chunk(&queue, &mut file, file_header_size, 4..8),
+        Err(PlyError::ChunkOutOfBounds)
+    ));
+    assert_eq!(scene.revision(), revision);
+    assert_eq!(scene.splat_data, splat_data);
+    assert_eq!(read_splat_buffer(&device, &queue, &scene), transmute_slice::<_, u8>(&splat_data[0..4]));
+}
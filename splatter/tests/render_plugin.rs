@@ -0,0 +1,101 @@
+use bevy::{asset::AssetId, prelude::*, render::render_resource::TextureFormat};
+use splatter::{
+    component::{GaussianSplatCameraSettings, GaussianSplatSettings},
+    render_plugin::{ExtractedGaussianSplat, RendererUpdate, ViewKey},
+    renderer::{Configuration, DepthSorting},
+    utils::transmute_slice,
+};
+
+fn extracted_splat(transform: Transform) -> ExtractedGaussianSplat {
+    ExtractedGaussianSplat {
+        cloud: AssetId::invalid(),
+        transform: transform.into(),
+        settings: GaussianSplatSettings::default(),
+    }
+}
+
+#[test]
+fn model_transform_matches_global_transform() {
+    for transform in [
+        Transform::IDENTITY,
+        Transform::from_xyz(1.0, -2.0, 3.0),
+        Transform::from_rotation(Quat::from_rotation_y(0.7)),
+        Transform::from_xyz(-0.5, 4.0, 2.5)
+            .with_rotation(Quat::from_euler(EulerRot::XYZ, 0.3, -1.2, 2.1))
+            .with_scale(Vec3::splat(2.5)),
+    ] {
+        let splat = extracted_splat(transform);
+        let matrix = splat.model_transform().matrix();
+        let expected = splat.transform.compute_matrix().to_cols_array();
+        for (value, expected_value) in transmute_slice::<_, f32>(&matrix).iter().zip(expected) {
+            assert!((value - expected_value).abs() < 1.0e-5, "{:?} differs from {:?}", matrix, expected);
+        }
+    }
+}
+
+#[test]
+fn model_transform_averages_non_uniform_scales() {
+    let splat = extracted_splat(Transform::from_scale(Vec3::new(1.0, 2.0, -4.0)));
+    assert!((splat.model_transform().scale - 2.0).abs() < 1.0e-5);
+}
+
+#[test]
+fn renderer_update_follows_view_key_and_configuration() {
+    let key = ViewKey {
+        format: TextureFormat::Rgba16Float,
+        sample_count: 4,
+        settings: GaussianSplatCameraSettings::default(),
+    };
+    let config = Configuration::default();
+    let previous_config = key.config(&config);
+    assert_eq!(key.renderer_update(None, &config, false), RendererUpdate::Rebuild);
+    assert_eq!(key.renderer_update(Some((&key, &previous_config)), &config, false), RendererUpdate::Keep);
+    // Only options which are not baked into the pipelines and buffers
+    let rescaled = Configuration {
+        splat_scale: 0.5,
+        ..Configuration::default()
+    };
+    assert_eq!(
+        key.renderer_update(Some((&key, &previous_config)), &rescaled, true),
+        RendererUpdate::SetConfig
+    );
+    let resorted = Configuration {
+        depth_sorting: DepthSorting::Cpu,
+        ..Configuration::default()
+    };
+    assert_eq!(
+        key.renderer_update(Some((&key, &previous_config)), &resorted, true),
+        RendererUpdate::Rebuild
+    );
+    for other_key in [
+        ViewKey {
+            format: TextureFormat::Rgba8UnormSrgb,
+            ..key
+        },
+        ViewKey { sample_count: 1, ..key },
+        ViewKey {
+            settings: GaussianSplatCameraSettings { write_depth: true },
+            ..key
+        },
+    ] {
+        assert_eq!(
+            key.renderer_update(Some((&other_key, &previous_config)), &config, false),
+            RendererUpdate::Rebuild
+        );
+    }
+}
+
+#[test]
+fn view_key_overrides_the_configuration() {
+    let key = ViewKey {
+        format: TextureFormat::Rgba16Float,
+        sample_count: 4,
+        settings: GaussianSplatCameraSettings { write_depth: true },
+    };
+    let config = key.config(&Configuration::default());
+    assert_eq!(config.surface_configuration.format, TextureFormat::Rgba16Float);
+    assert_eq!(config.sample_count, 4);
+    assert!(config.depth_format.is_some());
+    assert!(config.reversed_depth);
+    assert!(config.write_depth);
+}
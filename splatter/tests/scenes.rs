@@ -0,0 +1,144 @@
+mod common;
+
+use common::{camera, config, device, scene_of, splat, test_splats, total_difference};
+use geometric_algebra::{
+    ppga3d::{Rotor, Translator},
+    GeometricProduct, One,
+};
+use splatter::{
+    cpu_renderer::CpuRenderer,
+    renderer::{DepthSorting, GatheredSplats, ModelTransform, Renderer},
+    shader::ShaderCache,
+};
+use std::sync::Arc;
+
+/// Not a multiple of 64 pixels, so that the rows of the readback are padded
+const WIDTH: u32 = 67;
+const HEIGHT: u32 = 45;
+
+#[test]
+fn sorts_interleaved_scenes_together() {
+    let Some((device, queue)) = device() else {
+        eprintln!("no wgpu adapter available, skipping");
+        return;
+    };
+    // Translucent splats of the two scenes alternate in depth once the second one is moved back
+    let translucent = |center: [f32; 3], color: [f32; 3]| splat(center, [0.8, 0.6, 0.4], [0.9, 0.1, 0.2, 0.3], color, 0.6);
+    let front = scene_of(
+        &device,
+        vec![
+            translucent([0.0, 0.0, 4.0], [0.9, 0.1, 0.1]),
+            translucent([0.2, 0.1, 6.0], [0.9, 0.9, 0.1]),
+        ],
+    );
+    let back = scene_of(
+        &device,
+        vec![
+            translucent([-0.2, 0.0, 3.0], [0.1, 0.9, 0.1]),
+            translucent([0.1, -0.1, 5.0], [0.1, 0.1, 0.9]),
+        ],
+    );
+    let moved_back = ModelTransform {
+        motor: Translator::new(1.0, 0.0, 0.0, -0.5 * 2.0).geometric_product(Rotor::one()),
+        ..ModelTransform::default()
+    };
+    let scenes = [(&front, ModelTransform::default()), (&back, moved_back)];
+    let reference = CpuRenderer::new(config(DepthSorting::Cpu)).render(&camera(WIDTH, HEIGHT), WIDTH, HEIGHT, &scenes);
+    let back_only = CpuRenderer::new(config(DepthSorting::Cpu)).render(&camera(WIDTH, HEIGHT), WIDTH, HEIGHT, &scenes[1..]);
+    assert!(reference.chunks(4).any(|pixel| pixel[3] > 200));
+    assert_ne!(reference, back_only);
+    for depth_sorting in [DepthSorting::Cpu, DepthSorting::Gpu] {
+        let image =
+            Renderer::new(&device, config(depth_sorting)).render_scenes_to_image(&device, &queue, &scenes, &camera(WIDTH, HEIGHT), WIDTH, HEIGHT);
+        let total_difference = total_difference(&image, &reference);
+        assert!(
+            total_difference < reference.len() as u64,
+            "{:?} differs by {} in total",
+            depth_sorting,
+            total_difference
+        );
+    }
+}
+
+#[test]
+fn skips_scenes_which_do_not_fit() {
+    let Some((device, queue)) = device() else {
+        eprintln!("no wgpu adapter available, skipping");
+        return;
+    };
+    let scene = scene_of(&device, test_splats());
+    let too_large = scene_of(&device, vec![splat([0.0, 0.0, 5.0], [0.5; 3], [0.9, 0.1, 0.2, 0.3], [1.0; 3], 0.9); 2000]);
+    let renderer = Renderer::new(&device, config(DepthSorting::Cpu));
+    let expected = renderer.render_to_image(&device, &queue, &scene, &camera(WIDTH, HEIGHT), WIDTH, HEIGHT);
+    let scenes = [(&too_large, ModelTransform::default()), (&scene, ModelTransform::default())];
+    assert_eq!(
+        renderer.render_scenes_to_image(&device, &queue, &scenes, &camera(WIDTH, HEIGHT), WIDTH, HEIGHT),
+        expected
+    );
+}
+
+#[test]
+fn gathers_scenes_again_when_they_change() {
+    let Some((device, queue)) = device() else {
+        eprintln!("no wgpu adapter available, skipping");
+        return;
+    };
+    let mut scene = scene_of(&device, test_splats());
+    let renderer = Renderer::new(&device, config(DepthSorting::Cpu));
+    let before = renderer.render_to_image(&device, &queue, &scene, &camera(WIDTH, HEIGHT), WIDTH, HEIGHT);
+    assert_eq!(
+        renderer.render_to_image(&device, &queue, &scene, &camera(WIDTH, HEIGHT), WIDTH, HEIGHT),
+        before
+    );
+    let mut splats = scene.splat_data.clone();
+    for splat in &mut splats {
+        splat.color_sh[0..3].copy_from_slice(&[1.5; 3]);
+    }
+    scene.set_splats(splats);
+    scene.create_splat_buffer(&device);
+    let after = renderer.render_to_image(&device, &queue, &scene, &camera(WIDTH, HEIGHT), WIDTH, HEIGHT);
+    assert_ne!(after, before);
+    assert_eq!(
+        after,
+        Renderer::new(&device, config(DepthSorting::Cpu)).render_to_image(&device, &queue, &scene, &camera(WIDTH, HEIGHT), WIDTH, HEIGHT)
+    );
+}
+
+#[test]
+fn views_share_gathered_splats() {
+    let Some((device, queue)) = device() else {
+        eprintln!("no wgpu adapter available, skipping");
+        return;
+    };
+    let first = scene_of(&device, test_splats());
+    let second = scene_of(
+        &device,
+        vec![splat([0.5, -0.5, 4.0], [0.3, 0.5, 0.7], [0.9, 0.1, 0.2, 0.3], [0.2, 0.3, 0.9], 0.9)],
+    );
+    let third = scene_of(
+        &device,
+        vec![splat([-0.5, -0.5, 7.0], [0.9, 0.4, 0.6], [0.9, 0.1, 0.2, 0.3], [0.8, 0.8, 0.2], 0.9)],
+    );
+    let moved = ModelTransform {
+        motor: Translator::new(1.0, -0.5 * 0.5, 0.0, 0.0).geometric_product(Rotor::one()),
+        ..ModelTransform::default()
+    };
+    // Each view sees a different share of the scenes, placing the same scene twice does not store it twice
+    let view_scenes = [
+        vec![(&first, ModelTransform::default()), (&third, ModelTransform::default())],
+        vec![(&second, ModelTransform::default()), (&third, ModelTransform::default()), (&third, moved)],
+    ];
+    let gathered_splats = Arc::new(GatheredSplats::new(&device, 6));
+    gathered_splats.gather(&device, &queue, &[&first, &second, &third]);
+    let mut shader_cache = ShaderCache::default();
+    for depth_sorting in [DepthSorting::Cpu, DepthSorting::Gpu, DepthSorting::GpuTiled] {
+        for scenes in &view_scenes {
+            let shared = Renderer::with_gathered_splats(&device, config(depth_sorting), &mut shader_cache, gathered_splats.clone())
+                .render_scenes_to_image(&device, &queue, scenes, &camera(WIDTH, HEIGHT), WIDTH, HEIGHT);
+            let standalone =
+                Renderer::new(&device, config(depth_sorting)).render_scenes_to_image(&device, &queue, scenes, &camera(WIDTH, HEIGHT), WIDTH, HEIGHT);
+            assert!(standalone.pixels().any(|pixel| pixel[3] > 0));
+            assert_eq!(shared, standalone, "{:?}", depth_sorting);
+        }
+    }
+}
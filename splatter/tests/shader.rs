@@ -0,0 +1,115 @@
+mod common;
+
+use common::device;
+use splatter::{
+    renderer::{Configuration, ConfigurationError, DepthSorting},
+    shader::{ShaderCache, ShaderKey, SortingLayout},
+};
+use std::sync::Arc;
+
+fn validate(config: &Configuration) {
+    let source = ShaderKey::new(config).source();
+    let module = naga::front::wgsl::parse_str(&source).unwrap_or_else(|error| panic!("{}", error.emit_to_string(&source)));
+    naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::all())
+        .validate(&module)
+        .unwrap();
+}
+
+#[test]
+fn specializations_are_valid() {
+    for depth_sorting in [
+        DepthSorting::None,
+        DepthSorting::Cpu,
+        DepthSorting::CpuBackground,
+        DepthSorting::Gpu,
+        DepthSorting::GpuIndirectDraw,
+        DepthSorting::GpuTiled,
+    ] {
+        for spherical_harmonics_order in 0..=3 {
+            for radix_bits_per_digit in [1, 2, 4, 8] {
+                validate(&Configuration {
+                    depth_sorting,
+                    spherical_harmonics_order,
+                    radix_bits_per_digit,
+                    use_covariance_for_scale: spherical_harmonics_order % 2 == 0,
+                    use_unaligned_rectangles: spherical_harmonics_order < 2,
+                    ..Configuration::default()
+                });
+            }
+        }
+    }
+}
+
+#[test]
+fn equal_configurations_share_a_key() {
+    let config = Configuration::default();
+    let resized = Configuration {
+        splat_scale: 2.0,
+        ellipse_margin: 3.0,
+        ..Configuration::default()
+    };
+    assert_eq!(ShaderKey::new(&config), ShaderKey::new(&resized));
+    let unsorted = Configuration {
+        depth_sorting: DepthSorting::None,
+        ..Configuration::default()
+    };
+    assert_ne!(ShaderKey::new(&config), ShaderKey::new(&unsorted));
+}
+
+#[test]
+fn depth_sorting_modes_share_a_shader() {
+    let config = |depth_sorting| Configuration {
+        depth_sorting,
+        ..Configuration::default()
+    };
+    let sorted = ShaderKey::new(&config(DepthSorting::Gpu));
+    for depth_sorting in [DepthSorting::Cpu, DepthSorting::CpuBackground, DepthSorting::GpuTiled] {
+        assert_eq!(ShaderKey::new(&config(depth_sorting)), sorted);
+    }
+    assert_ne!(ShaderKey::new(&config(DepthSorting::None)), sorted);
+    assert_ne!(ShaderKey::new(&config(DepthSorting::GpuIndirectDraw)), sorted);
+
+    let Some((device, _queue)) = device() else {
+        eprintln!("no wgpu adapter available, skipping");
+        return;
+    };
+    let mut shader_cache = ShaderCache::default();
+    let module = shader_cache.get(&device, &config(DepthSorting::Cpu));
+    assert!(Arc::ptr_eq(&module, &shader_cache.get(&device, &config(DepthSorting::Gpu))));
+    assert!(Arc::ptr_eq(&module, &shader_cache.get(&device, &config(DepthSorting::GpuTiled))));
+    assert!(!Arc::ptr_eq(&module, &shader_cache.get(&device, &config(DepthSorting::GpuIndirectDraw))));
+}
+
+#[test]
+fn sorting_layout() {
+    let layout = SortingLayout::new(&Configuration {
+        radix_bits_per_digit: 8,
+        max_splat_count: 1024,
+        ..Configuration::default()
+    });
+    assert_eq!(layout.radix_base, 256);
+    assert_eq!(layout.radix_digit_places, 4);
+    assert_eq!(layout.workgroup_entries_c, 1024);
+    assert_eq!(layout.max_tile_count_c, 1);
+    assert_eq!(layout.sorting_buffer_size, (256 * 5 + 5) * 4);
+}
+
+#[test]
+fn rejects_unsupported_radix_digits() {
+    // Zero, not a divisor of the 32 bit keys, an odd number of passes and too large for a workgroup
+    for radix_bits_per_digit in [0, 3, 6, 32, 16] {
+        let config = Configuration {
+            radix_bits_per_digit,
+            ..Configuration::default()
+        };
+        assert_eq!(
+            config.validate(),
+            Err(ConfigurationError::UnsupportedRadixBitsPerDigit(radix_bits_per_digit))
+        );
+        assert!(
+            std::panic::catch_unwind(|| SortingLayout::new(&config)).is_err(),
+            "{} bits were accepted",
+            radix_bits_per_digit
+        );
+    }
+}
@@ -0,0 +1,34 @@
+use splatter::scene::{splat_format::RECORD_SIZE, Scene};
+use std::io::Cursor;
+
+#[test]
+fn round_trip() {
+    let mut scene = Scene::new();
+    scene.load_splat_file(concat!(env!("CARGO_MANIFEST_DIR"), "/test.ply")).unwrap();
+    let mut data = Vec::new();
+    scene.save_splat(&mut data).unwrap();
+    assert_eq!(data.len(), scene.splat_count * RECORD_SIZE);
+
+    let mut loaded = Scene::new();
+    loaded.load_splat(&mut Cursor::new(&data)).unwrap();
+    assert_eq!(loaded.splat_count, scene.splat_count);
+    assert_eq!(loaded.splat_positions, scene.splat_positions);
+    for (original, loaded) in scene.splat_data.iter().zip(loaded.splat_data.iter()) {
+        assert_eq!(loaded.center, original.center);
+        assert_eq!(loaded.scale, original.scale);
+        assert_eq!(loaded.rotation, original.rotation);
+        assert!((loaded.alpha - original.alpha).abs() <= 0.5 / 255.0);
+        // One step of the 8 bit color quantization in SH0 space
+        let tolerance = 0.5 / 255.0 / 0.282_094_8;
+        for channel in 0..3 {
+            assert!((loaded.color_sh[channel] - original.color_sh[channel]).abs() <= tolerance);
+        }
+        assert!(loaded.color_sh[3..].iter().all(|coefficient| *coefficient == 0.0));
+    }
+}
+
+#[test]
+fn reject_truncated_file() {
+    let mut scene = Scene::new();
+    assert!(scene.load_splat(&mut Cursor::new(vec![0; RECORD_SIZE + 1])).is_err());
+}
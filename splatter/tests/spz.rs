@@ -0,0 +1,97 @@
+use splatter::scene::{LoadError, Scene, SpzError};
+use std::io::Cursor;
+
+fn assert_round_trip(scene: &Scene, loaded: &Scene) {
+    assert_eq!(loaded.splat_count, scene.splat_count);
+    for (original, loaded) in scene.splat_data.iter().zip(loaded.splat_data.iter()) {
+        for axis in 0..3 {
+            // Half a step of the 12 fractional bits
+            assert!((loaded.center[axis] - original.center[axis]).abs() <= 0.5 / 4096.0);
+            // Half a step of the 4 fractional bits of the log scale
+            assert!((loaded.scale[axis].ln() - original.scale[axis].ln()).abs() <= 0.5 / 16.0 + 1e-5);
+            assert!((loaded.color_sh[axis] - original.color_sh[axis]).abs() <= 0.5 / 255.0 / 0.15 + 1e-5);
+        }
+        let dot = loaded.rotation.iter().zip(original.rotation.iter()).map(|(a, b)| a * b).sum::<f32>();
+        assert!(dot.abs() > 0.999);
+        assert!((loaded.alpha - original.alpha).abs() <= 0.5 / 255.0);
+        for (index, (loaded, original)) in loaded.color_sh[3..].iter().zip(original.color_sh[3..].iter()).enumerate() {
+            // The first band is quantized to 5 bits, the higher bands to 4 bits
+            let tolerance = if index < 9 { 4.5 / 128.0 } else { 8.5 / 128.0 };
+            assert!((loaded - original).abs() <= tolerance);
+        }
+    }
+}
+
+#[test]
+fn round_trip() {
+    let mut scene = Scene::new();
+    scene.load_splat_file(concat!(env!("CARGO_MANIFEST_DIR"), "/test.ply")).unwrap();
+    let mut data = Vec::new();
+    scene.save_spz(&mut data).unwrap();
+    // Gzip magic number
+    assert_eq!(data[0..2], [0x1f, 0x8b]);
+
+    let mut loaded = Scene::new();
+    loaded.load_spz(&mut Cursor::new(&data)).unwrap();
+    assert_round_trip(&scene, &loaded);
+    assert!(loaded
+        .splat_data
+        .iter()
+        .all(|splat| splat.color_sh[3..].iter().all(|coefficient| *coefficient == 0.0)));
+}
+
+#[test]
+fn round_trip_spherical_harmonics() {
+    let mut scene = Scene::new();
+    scene.load_splat_file(concat!(env!("CARGO_MANIFEST_DIR"), "/test.ply")).unwrap();
+    let mut splats = scene.splat_data.clone();
+    for (index, splat) in splats.iter_mut().enumerate() {
+        for (coefficient, value) in splat.color_sh[3..].iter_mut().enumerate() {
+            *value = ((index * 7 + coefficient * 3) % 17) as f32 / 8.0 - 1.0;
+        }
+    }
+    scene.set_splats(splats);
+    let mut data = Vec::new();
+    scene.save_spz(&mut data).unwrap();
+
+    let mut loaded = Scene::new();
+    loaded.load_spz(&mut Cursor::new(&data)).unwrap();
+    assert_round_trip(&scene, &loaded);
+}
+
+#[test]
+fn reject_invalid_magic() {
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    std::io::Write::write_all(&mut encoder, &[0; 16]).unwrap();
+    let data = encoder.finish().unwrap();
+    let mut scene = Scene::new();
+    assert!(matches!(scene.load_spz(&mut Cursor::new(&data)), Err(SpzError::InvalidMagic(0))));
+    let error: LoadError = scene.load_spz(&mut Cursor::new(&data[0..8])).unwrap_err().into();
+    assert!(matches!(error, LoadError::Spz(SpzError::Io(_))));
+}
+
+#[test]
+fn reject_malformed_header() {
+    let compress = |header: &[u8]| {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        std::io::Write::write_all(&mut encoder, header).unwrap();
+        encoder.finish().unwrap()
+    };
+    let header = |count: u32, fractional_bits: u8| {
+        let mut header = b"NGSP".to_vec();
+        header.extend_from_slice(&3u32.to_le_bytes());
+        header.extend_from_slice(&count.to_le_bytes());
+        header.extend_from_slice(&[0, fractional_bits, 0, 0]);
+        header
+    };
+    let mut scene = Scene::new();
+    assert!(matches!(
+        scene.load_spz(&mut Cursor::new(compress(&header(1, 32)))),
+        Err(SpzError::UnsupportedFractionalBits(32))
+    ));
+    // Must fail without trying to allocate memory for the declared splats
+    match scene.load_spz(&mut Cursor::new(compress(&header(u32::MAX, 12)))) {
+        Err(SpzError::Io(error)) => assert_eq!(error.kind(), std::io::ErrorKind::UnexpectedEof),
+        result => panic!("unexpected result {:?}", result.map(|_| ())),
+    }
+}
@@ -0,0 +1,112 @@
+mod common;
+
+use common::{camera, config, device, render_frame_with_depth, scene_of, splat, test_splats, total_difference};
+use splatter::{
+    renderer::{Configuration, DepthSorting, Renderer},
+    scene::GpuSplat,
+};
+
+/// Not a multiple of 64 pixels, so that the rows of the readback are padded
+const WIDTH: u32 = 67;
+const HEIGHT: u32 = 45;
+
+#[test]
+fn tiled_matches_rasterizer_with_overdraw() {
+    let Some((device, queue)) = device() else {
+        eprintln!("no wgpu adapter available, skipping");
+        return;
+    };
+    // Hundreds of splats per tile, so that sorting them takes several passes per invocation and pixels saturate early
+    let mut state = 7u32;
+    let mut random = || {
+        state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        (state >> 8) as f32 / (1 << 24) as f32
+    };
+    let splats = (0..1000)
+        .map(|_| {
+            let mut color_sh = [0.0; 48];
+            for coefficient in &mut color_sh[0..3] {
+                *coefficient = (random() - 0.5) / 0.282_094_8;
+            }
+            let rotation = [0.5 + random(), random() - 0.5, random() - 0.5, random() - 0.5];
+            let norm = rotation.iter().map(|value| value * value).sum::<f32>().sqrt();
+            GpuSplat {
+                rotation: rotation.map(|value| value / norm),
+                center: [random() * 3.0 - 1.5, random() * 2.0 - 1.0, 4.0 + random() * 4.0],
+                scale: [0.1 + random() * 0.5, 0.1 + random() * 0.5, 0.1 + random() * 0.5],
+                alpha: 0.2 + random() * 0.6,
+                color_sh,
+                ..GpuSplat::default()
+            }
+        })
+        .collect();
+    let scene = scene_of(&device, splats);
+    let [rasterized, tiled] = [DepthSorting::Cpu, DepthSorting::GpuTiled].map(|depth_sorting| {
+        Renderer::new(&device, config(depth_sorting)).render_to_image(&device, &queue, &scene, &camera(WIDTH, HEIGHT), WIDTH, HEIGHT)
+    });
+    // Early termination leaves almost opaque pixels slightly transparent
+    assert!(tiled.pixels().filter(|pixel| pixel.0[3] > 250).count() > 100);
+    // Sorting by the depth of each splat instead of globally and stopping at almost opaque pixels both change the result a little
+    let total_difference = total_difference(&tiled, &rasterized);
+    assert!(
+        total_difference < rasterized.as_raw().len() as u64 * 2,
+        "differs by {} in total",
+        total_difference
+    );
+}
+
+#[test]
+fn tiled_grows_tile_entries() {
+    let Some((device, queue)) = device() else {
+        eprintln!("no wgpu adapter available, skipping");
+        return;
+    };
+    // Each splat covers all 15 tiles, so they need more entries than TILE_ENTRIES_PER_SPLAT per splat
+    let splats = (0..800)
+        .map(|index| {
+            splat(
+                [0.0, 0.0, 5.0 + index as f32 * 0.004],
+                [4.0, 4.0, 0.5],
+                [0.9, 0.1, 0.2, 0.3],
+                [(index % 7) as f32 / 7.0, 0.5, 0.8],
+                0.3,
+            )
+        })
+        .collect();
+    let scene = scene_of(&device, splats);
+    let [rasterized, tiled] = [DepthSorting::Cpu, DepthSorting::GpuTiled].map(|depth_sorting| {
+        Renderer::new(&device, config(depth_sorting)).render_to_image(&device, &queue, &scene, &camera(WIDTH, HEIGHT), WIDTH, HEIGHT)
+    });
+    // Without growing, the tiles after the capacity ran out would stay empty
+    assert!(rasterized
+        .pixels()
+        .zip(tiled.pixels())
+        .all(|(rasterized, tiled)| rasterized[3] < 10 || tiled[3] > 0));
+    let total_difference = total_difference(&tiled, &rasterized);
+    assert!(
+        total_difference < rasterized.as_raw().len() as u64 * 2,
+        "differs by {} in total",
+        total_difference
+    );
+}
+
+#[test]
+fn tiled_ignores_the_depth_view() {
+    let Some((device, queue)) = device() else {
+        eprintln!("no wgpu adapter available, skipping");
+        return;
+    };
+    let scene = scene_of(&device, test_splats());
+    let renderer = Renderer::new(
+        &device,
+        Configuration {
+            depth_format: Some(wgpu::TextureFormat::Depth32Float),
+            reversed_depth: true,
+            write_depth: true,
+            ..config(DepthSorting::GpuTiled)
+        },
+    );
+    let behind_everything = render_frame_with_depth(&device, &queue, &renderer, &scene, 0.0);
+    assert!(behind_everything.chunks(4).any(|pixel| pixel[0..3] != [0; 3]));
+    assert_eq!(render_frame_with_depth(&device, &queue, &renderer, &scene, 1.0), behind_everything);
+}